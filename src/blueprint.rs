@@ -0,0 +1,180 @@
+use super::commands::{absolute, isexists};
+use super::gresource::{walk, GResource};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// .blp files under sourcedir
+pub fn sources(sourcedir: &Path) -> Vec<PathBuf> {
+    walk(sourcedir)
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("blp")))
+        .collect()
+}
+
+// sourcedir/dialogs/about.blp compiles to outdir/dialogs/about.ui
+pub fn target(sourcedir: &Path, outdir: &Path, source: &Path) -> PathBuf {
+    outdir
+        .join(source.strip_prefix(sourcedir).unwrap_or(source))
+        .with_extension("ui")
+}
+
+pub fn compile(sourcedir: &Path, outdir: &Path, sources: &[PathBuf]) -> Option<()> {
+    if sources.is_empty() {
+        return Some(());
+    }
+    isexists("blueprint-compiler")?;
+    std::fs::create_dir_all(outdir).ok()?;
+
+    let output = Command::new("blueprint-compiler")
+        .arg("batch-compile")
+        .arg(outdir)
+        .arg(sourcedir)
+        .args(sources)
+        .output()
+        .ok()?;
+    report(&String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+    if output.status.success() {
+        Some(())
+    } else {
+        None
+    }
+}
+
+// Point .blp entries at the compiled .ui, inside the bundle
+// window.blp becomes window.ui unless it has an alias.
+pub fn rewrite(gresource: &mut GResource, sourcedir: &Path, outdir: &Path) {
+    for file in &mut gresource.files {
+        let ui = match file.path.strip_suffix(".blp") {
+            Some(stem) => stem.to_string() + ".ui",
+            None => continue,
+        };
+        let source = sourcedir.join(&file.path);
+        file.path = absolute(&target(sourcedir, outdir, &source))
+            .to_string_lossy()
+            .into_owned();
+        file.alias.get_or_insert(ui);
+    }
+}
+
+fn report(output: &str) {
+    print!("{}", reformat(output));
+}
+
+// blueprint-compiler prints "error: message" followed by
+// "at file line 5 column 3:", print those as file:5:3
+// like rustc does and pass everything else through.
+fn reformat(output: &str) -> String {
+    let lines = output.lines().map(strip_colors).collect::<Vec<_>>();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let location = lines.get(i + 1).and_then(|next| location(next));
+        match (lines[i].split_once(": "), location) {
+            (Some((category, message)), Some(location)) if !category.contains(' ') => {
+                let color = if category == "error" { "1;31" } else { "1;33" };
+                out.push_str(&format!(
+                    "\x1b[{}m{}\x1b[0m: {}: {}\n",
+                    color, category, location, message
+                ));
+                i += 2;
+            }
+            _ => {
+                out.push_str(&lines[i]);
+                out.push('\n');
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// "at data/resources/window.blp line 5 column 3:"
+fn location(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("at ")?.strip_suffix(':')?;
+    let (file, rest) = rest.rsplit_once(" line ")?;
+    let (line, column) = rest.split_once(" column ")?;
+    Some(format!("{}:{}:{}", file, line, column))
+}
+
+// Colors would end up in the middle of the reformatted lines
+fn strip_colors(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to the final letter of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As printed by blueprint-compiler batch-compile
+    const OUTPUT: &str = "\
+\x1b[91m\x1b[1merror: Could not determine what kind of syntax is meant here\x1b[0m
+at data/resources/window.blp line 5 column 3:
+\x1b[2m   5 |\x1b[0m  lable: \"Hello\";
+     \x1b[2m|  ^^^^^^\x1b[0m
+\x1b[33m\x1b[1mwarning: gtk version 4.0 is deprecated\x1b[0m
+at data/resources/dialogs/about dialog.blp line 1 column 1:
+\x1b[2m   1 |\x1b[0musing Gtk 4.0;
+     \x1b[2m|^^^^^\x1b[0m
+Compiled 2 files
+";
+
+    #[test]
+    fn strips_colors() {
+        assert_eq!(
+            strip_colors("\x1b[91m\x1b[1merror: oops\x1b[0m"),
+            "error: oops"
+        );
+        assert_eq!(strip_colors("plain: text"), "plain: text");
+    }
+
+    #[test]
+    fn locations() {
+        assert_eq!(
+            location("at data/resources/window.blp line 5 column 3:").as_deref(),
+            Some("data/resources/window.blp:5:3")
+        );
+        assert_eq!(
+            location("at my line 2 line 7 column 12:").as_deref(),
+            Some("my line 2:7:12")
+        );
+        assert_eq!(location("at window.blp line 5:"), None);
+        assert_eq!(location("Compiled 2 files"), None);
+    }
+
+    #[test]
+    fn reformats_diagnostics() {
+        assert_eq!(
+            reformat(OUTPUT),
+            "\
+\x1b[1;31merror\x1b[0m: data/resources/window.blp:5:3: Could not determine what kind of syntax is meant here
+   5 |  lable: \"Hello\";
+     |  ^^^^^^
+\x1b[1;33mwarning\x1b[0m: data/resources/dialogs/about dialog.blp:1:1: gtk version 4.0 is deprecated
+   1 |using Gtk 4.0;
+     |^^^^^
+Compiled 2 files
+"
+        );
+        // Without a location the message is passed through
+        assert_eq!(reformat("error: crashed\n"), "error: crashed\n");
+    }
+}
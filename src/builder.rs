@@ -0,0 +1,885 @@
+use super::blueprint;
+use super::commands::absolute;
+use super::commands::Commands;
+use super::fingerprint::{read_dir_sorted, Fingerprints, Hasher};
+use super::gettext;
+use super::gresource::GResource;
+use super::gschema::is_schema_file;
+use super::metadata::{Hook, Metadata};
+use super::pseudo;
+#[cfg(target_os = "linux")]
+use super::runtime::{self, Launch};
+use super::scss;
+#[cfg(target_os = "linux")]
+use super::watch::Watcher;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// Packaging steps, in the order build() runs them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    ConfigFiles,
+    Translations,
+    AppdataDesktop,
+    Blueprints,
+    Stylesheets,
+    Resources,
+    Icons,
+    Schemas,
+    Config,
+    Binary,
+    // A command from [package.metadata.pkg.hooks]
+    Hook(Hook),
+}
+
+impl Step {
+    pub const ALL: &'static [Step] = &[
+        Step::ConfigFiles,
+        Step::Translations,
+        Step::AppdataDesktop,
+        Step::Blueprints,
+        Step::Stylesheets,
+        Step::Resources,
+        Step::Icons,
+        Step::Schemas,
+        Step::Config,
+        Step::Binary,
+    ];
+
+    // Used by --skip and to position hooks
+    pub fn name(&self) -> &str {
+        match self {
+            Step::ConfigFiles => "config-files",
+            Step::Translations => "translations",
+            Step::AppdataDesktop => "appdata",
+            Step::Blueprints => "blueprints",
+            Step::Stylesheets => "stylesheets",
+            Step::Resources => "resources",
+            Step::Icons => "icons",
+            Step::Schemas => "schemas",
+            Step::Config => "config",
+            Step::Binary => "binary",
+            Step::Hook(hook) => &hook.name,
+        }
+    }
+
+    // Status and name printed while running, the
+    // name also identifies the step's fingerprint.
+    pub fn banner(&self) -> (&'static str, &str) {
+        match self {
+            Step::ConfigFiles => ("Processing", ".in files"),
+            Step::Translations => ("Compiling", "langauge files"),
+            Step::AppdataDesktop => ("Generating", "appdata and desktop files"),
+            Step::Blueprints => ("Compiling", "blueprint files"),
+            Step::Stylesheets => ("Compiling", "stylesheets"),
+            Step::Resources => ("Installing", "glib resources"),
+            Step::Icons => ("Installing", "icon files"),
+            Step::Schemas => ("Installing", "glib schemas"),
+            Step::Config => ("Generating", "config.rs file"),
+            Step::Binary => ("Installing", "binary"),
+            Step::Hook(hook) => ("Running", &hook.name),
+        }
+    }
+
+    // config.rs is always regenerated as it exports CONFIG_PATH,
+    // the file is only rewritten when its contents change.
+    // Hooks without inputs can't tell whether they're needed.
+    fn always_runs(&self) -> bool {
+        match self {
+            Step::Config => true,
+            Step::Hook(hook) => hook.inputs.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+// What a step reads and writes, and whether it would be skipped
+#[derive(Debug, Clone)]
+pub struct PlannedStep {
+    pub step: Step,
+    pub inputs: Vec<PathBuf>,
+    pub outputs: Vec<PathBuf>,
+    pub fresh: bool,
+}
+
+pub struct Builder<'a> {
+    // Profile can be either "release" or "debug"
+    profile: &'a str,
+    buildflags: &'a [String],
+    // Ignore fingerprints and run every step
+    force: bool,
+    // The crate's directory, data, po and src are looked up in it
+    root: PathBuf,
+    datadir: PathBuf,
+    podir: PathBuf,
+    // Steps build() runs, all of them by default
+    pub steps: Vec<Step>,
+    // Languages translated less than this aren't installed
+    pub min_percent: u32,
+    // Install a generated en@pseudo locale as well
+    pub pseudo_locale: bool,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(buildflags: &'a [String], profile: &'a str, force: bool) -> Self {
+        Builder {
+            buildflags,
+            profile,
+            force,
+            root: PathBuf::from("."),
+            datadir: PathBuf::from("data"),
+            podir: PathBuf::from("po"),
+            steps: Step::ALL.to_vec(),
+            min_percent: 0,
+            pseudo_locale: false,
+        }
+    }
+
+    // Build the crate in root instead of the current directory
+    pub fn with_root(mut self, root: &Path) -> Self {
+        self.root = root.to_path_buf();
+        self.datadir = root.join("data");
+        self.podir = root.join("po");
+        self
+    }
+
+    fn commands<'b>(&'b self, metadata: &'b Metadata) -> Commands<'b> {
+        Commands {
+            root: &self.root,
+            datadir: &self.datadir,
+            podir: &self.podir,
+            outdir: self.outdir(metadata),
+            metadata,
+            profile: self.profile,
+            min_percent: self.min_percent,
+            pseudo_locale: self.pseudo_locale,
+        }
+    }
+
+    fn outdir(&self, metadata: &Metadata) -> PathBuf {
+        metadata.targetdir.join(self.profile).join("data")
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.steps.iter().position(|step| step.name() == name)
+    }
+
+    // Remove the step called name, false if there is none
+    pub fn skip(&mut self, name: &str) -> bool {
+        let position = self.position(name);
+        if let Some(index) = position {
+            self.steps.remove(index);
+        }
+        position.is_some()
+    }
+
+    // Insert hooks before or after the step they name, in the order
+    // they're declared. Hooks which don't name one run after all others.
+    pub fn add_hooks(&mut self, hooks: &[Hook]) {
+        for hook in hooks {
+            let position = match (&hook.before, &hook.after) {
+                (Some(name), _) => self.position(name),
+                (None, Some(name)) => self.position(name).map(|mut index| {
+                    // After earlier hooks of the same step
+                    index += 1;
+                    while matches!(self.steps.get(index), Some(Step::Hook(other))
+                        if other.before.is_none() && other.after == hook.after)
+                    {
+                        index += 1;
+                    }
+                    index
+                }),
+                (None, None) => Some(self.steps.len()),
+            };
+            let index = position.unwrap_or_else(|| {
+                println!(
+                    "\x1b[1;33mwarning\x1b[0m: hook \"{}\" refers to an unknown step, running it last",
+                    hook.name
+                );
+                self.steps.len()
+            });
+            self.steps.insert(index, Step::Hook(hook.clone()));
+        }
+    }
+
+    // Steps with their files as things are now. Inputs made
+    // by earlier steps are the ones from the last build.
+    pub fn plan(&self, metadata: &Metadata, prefix: &Path) -> Vec<PlannedStep> {
+        let fingerprints = Fingerprints::load(&self.outdir(metadata), self.force);
+        self.steps
+            .iter()
+            .map(|step| {
+                let (inputs, outputs) = self.files(step, metadata, prefix);
+                let hash = self.hash(step, metadata, prefix, &inputs, &outputs);
+                PlannedStep {
+                    fresh: !step.always_runs() && fingerprints.is_fresh(step.banner().1, hash),
+                    step: step.clone(),
+                    inputs,
+                    outputs,
+                }
+            })
+            .collect()
+    }
+
+    pub fn build(&self, metadata: &Metadata, prefix: &Path) -> bool {
+        let outdir = self.outdir(metadata);
+        if std::fs::create_dir_all(&outdir).is_err() {
+            println!(
+                "\x1b[1;31merror\x1b[0m: Error creating {} directory",
+                outdir.display()
+            );
+            return false;
+        }
+
+        let mut fingerprints = Fingerprints::load(&outdir, self.force);
+
+        for step in &self.steps {
+            let (status, name) = step.banner();
+            // Files are looked up right before running
+            // as earlier steps may have produced them.
+            let (inputs, outputs) = self.files(step, metadata, prefix);
+            let hash = || self.hash(step, metadata, prefix, &inputs, &outputs);
+
+            if !step.always_runs() && fingerprints.is_fresh(name, hash()) {
+                println!("\x1b[1;38;5;29m{:>12}\x1b[0m {}", "Fresh", name);
+                continue;
+            }
+
+            println!("\x1b[1;38;5;29m{:>12}\x1b[0m {}", status, name);
+            if self.run(step, metadata, prefix, &inputs).is_none() {
+                println!(
+                    "\x1b[1;31merror\x1b[0m: Error {} {}",
+                    status.to_lowercase(),
+                    name
+                );
+                return false;
+            }
+
+            // Don't remember steps which failed to produce their outputs
+            if outputs.iter().all(|path| path.exists()) {
+                fingerprints.update(name, hash());
+            }
+        }
+        true
+    }
+
+    // Everything a step depends on besides its files
+    fn hash(
+        &self,
+        step: &Step,
+        metadata: &Metadata,
+        prefix: &Path,
+        inputs: &[PathBuf],
+        outputs: &[PathBuf],
+    ) -> u64 {
+        let mut hasher = Hasher::default();
+        hasher
+            .str(&metadata.id)
+            .str(&metadata.bin)
+            .str(&metadata.gettext_domain)
+            .str(&metadata.name)
+            .str(&metadata.version)
+            .str(self.profile)
+            .str(&prefix.to_string_lossy());
+        for flag in self.buildflags {
+            hasher.str(flag);
+        }
+        hasher.str(step.banner().1);
+        match step {
+            Step::Hook(hook) => {
+                hasher.str(&hook.command);
+            }
+            Step::Translations => {
+                hasher
+                    .str(&self.min_percent.to_string())
+                    .str(&self.pseudo_locale.to_string());
+            }
+            // A plain cargo build since the last install
+            Step::Binary => {
+                hasher.output(&metadata.targetdir.join(self.profile).join(&metadata.bin));
+            }
+            _ => {}
+        }
+        for path in inputs {
+            hasher.input(path);
+        }
+        for path in outputs {
+            hasher.output(path);
+        }
+        hasher.finish()
+    }
+
+    // Inputs and outputs of a step
+    fn files(
+        &self,
+        step: &Step,
+        metadata: &Metadata,
+        prefix: &Path,
+    ) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let commands = self.commands(metadata);
+        let outdir = self.outdir(metadata);
+
+        match step {
+            Step::ConfigFiles => {
+                let configs = read_dir_sorted(&self.datadir)
+                    .into_iter()
+                    .filter(|path| path.is_file())
+                    .collect::<Vec<_>>();
+                let processed = configs
+                    .iter()
+                    .filter_map(|path| match path.extension() {
+                        Some(ext) if ext == "in" => Some(outdir.join(path.file_stem()?)),
+                        _ => Some(outdir.join(path.file_name()?)),
+                    })
+                    .collect();
+                (configs, processed)
+            }
+            Step::Translations => {
+                let mut languages = gettext::catalogs(&self.podir)
+                    .into_iter()
+                    .filter(|path| commands.below_min_percent(path).is_none())
+                    .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+                    .collect::<Vec<_>>();
+                if self.pseudo_locale {
+                    languages.push(pseudo::LOCALE.to_string());
+                }
+                let catalogs = languages
+                    .into_iter()
+                    .map(|name| {
+                        prefix
+                            .join("share/locale")
+                            .join(name)
+                            .join("LC_MESSAGES")
+                            .join(metadata.gettext_domain.clone() + ".mo")
+                    })
+                    .collect();
+                (vec![self.podir.clone()], catalogs)
+            }
+            Step::AppdataDesktop => {
+                let appdata = outdir.join(metadata.id.clone() + ".appdata.xml");
+                let desktop = outdir.join(metadata.id.clone() + ".desktop");
+                let mut installed = Vec::new();
+                if appdata.exists() {
+                    installed.push(
+                        prefix
+                            .join("share/appdata")
+                            .join(appdata.file_name().unwrap()),
+                    );
+                }
+                if desktop.exists() {
+                    installed.push(
+                        prefix
+                            .join("share/applications")
+                            .join(desktop.file_name().unwrap()),
+                    );
+                }
+                (vec![appdata, desktop, self.podir.clone()], installed)
+            }
+            Step::Blueprints => {
+                let resourcedir = self.datadir.join("resources");
+                let sources = blueprint::sources(&resourcedir);
+                let compiled = sources
+                    .iter()
+                    .map(|source| {
+                        blueprint::target(&resourcedir, &commands.blueprint_dir(), source)
+                    })
+                    .collect();
+                (sources, compiled)
+            }
+            Step::Stylesheets => {
+                let resourcedir = self.datadir.join("resources");
+                let sources = scss::sources(&resourcedir);
+                let compiled = scss::targets(&resourcedir, &commands.stylesheet_dir(), &sources);
+                (sources, compiled)
+            }
+            Step::Resources => {
+                let glibresource = outdir.join(metadata.id.clone() + ".gresource.xml");
+                let resourcedir = self.datadir.join("resources");
+                let mut installed = Vec::new();
+                // Only files referenced by the bundle are inputs
+                let mut resources = vec![glibresource.clone()];
+                if let Some(mut gresource) = GResource::from(&glibresource) {
+                    commands.rewrite_compiled(&mut gresource);
+                    resources.extend(gresource.dependencies(&resourcedir));
+                }
+                if glibresource.exists() && resourcedir.exists() {
+                    installed.push(commands.gresource_target(prefix));
+                }
+                (resources, installed)
+            }
+            Step::Icons => {
+                let icondir = self.datadir.join("icons");
+                let mut installed = Vec::new();
+                let scalable = icondir.join(metadata.id.clone() + ".svg");
+                let symbolic = icondir.join(metadata.id.clone() + "-symbolic.svg");
+                if scalable.exists() && symbolic.exists() {
+                    let icons = prefix.join("share/icons/hicolor");
+                    installed.push(
+                        icons
+                            .join("scalable/apps")
+                            .join(scalable.file_name().unwrap()),
+                    );
+                    installed.push(
+                        icons
+                            .join("symbolic/apps")
+                            .join(symbolic.file_name().unwrap()),
+                    );
+                }
+                (vec![icondir], installed)
+            }
+            Step::Schemas => {
+                // Processed by process_config_files
+                let gschemas = read_dir_sorted(&outdir)
+                    .into_iter()
+                    .filter(|path| is_schema_file(path))
+                    .collect::<Vec<_>>();
+                let mut installed = Vec::new();
+                if !gschemas.is_empty() {
+                    let schemadir = prefix.join("share/glib-2.0/schemas");
+                    for gschema in &gschemas {
+                        installed.push(schemadir.join(gschema.file_name().unwrap()));
+                    }
+                    installed.push(schemadir.join("gschemas.compiled"));
+                }
+                (gschemas, installed)
+            }
+            Step::Config => (Vec::new(), vec![outdir.join("config.rs")]),
+            // Files include_bytes! reads outside of these aren't seen,
+            // --force-rebuild reinstalls the binary then.
+            Step::Binary => {
+                let crates = metadata.path_dependencies.iter().flat_map(|dir| {
+                    ["src", "build.rs", "Cargo.toml"]
+                        .iter()
+                        .map(move |name| dir.join(name))
+                });
+                let sources = ["src", "build.rs", "Cargo.toml", "Cargo.lock"]
+                    .iter()
+                    .map(|name| self.root.join(name))
+                    .chain(crates)
+                    .chain(Some(outdir.join("config.rs")))
+                    .chain(
+                        Some(commands.gresource_target(prefix))
+                            .filter(|_| metadata.embed_resources),
+                    )
+                    .collect();
+                (sources, vec![prefix.join("bin").join(&metadata.bin)])
+            }
+            Step::Hook(hook) => {
+                let expand = |paths: &[String]| {
+                    paths
+                        .iter()
+                        .map(|path| self.root.join(expand(path, prefix, &outdir)))
+                        .collect()
+                };
+                (expand(&hook.inputs), expand(&hook.outputs))
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        step: &Step,
+        metadata: &Metadata,
+        prefix: &Path,
+        inputs: &[PathBuf],
+    ) -> Option<()> {
+        let commands = self.commands(metadata);
+        let outdir = self.outdir(metadata);
+
+        match step {
+            Step::ConfigFiles => commands.process_config_files(&outdir),
+            Step::Translations => commands.install_langauge_files(prefix),
+            Step::AppdataDesktop => {
+                commands.install_appdata_and_desktop(&inputs[0], &inputs[1], prefix)
+            }
+            Step::Blueprints => commands.compile_blueprints(inputs),
+            Step::Stylesheets => commands.compile_stylesheets(inputs),
+            Step::Resources => commands.install_glib_resources(&inputs[0], prefix),
+            Step::Icons => commands.install_icon_files(prefix),
+            Step::Schemas => commands.install_glib_schemas(inputs, prefix),
+            Step::Config => commands.generate_config_rs(&outdir, prefix),
+            Step::Binary => {
+                let out = commands.install_binary(self.buildflags, prefix)?;
+                if out.code() != Some(101) {
+                    Some(())
+                } else {
+                    None
+                }
+            }
+            Step::Hook(hook) => {
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(&hook.command)
+                    .current_dir(&self.root)
+                    .env("PREFIX", absolute(prefix))
+                    .env("OUTDIR", absolute(&outdir))
+                    .env("PROFILE", self.profile)
+                    .status()
+                    .ok()?;
+                if status.success() {
+                    Some(())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // Rebuild whenever src, data or po change and restart the app,
+    // fingerprints make build() rerun only the affected steps.
+    // Debug builds reload stylesheets on their own, changed ones
+    // are only recompiled.
+    #[cfg(target_os = "linux")]
+    pub fn watch(
+        &self,
+        metadata: &Metadata,
+        prefix: &Path,
+        launch: &Launch,
+        appargs: &[String],
+    ) -> ! {
+        let dirs = [
+            self.root.join("src"),
+            self.datadir.clone(),
+            self.podir.clone(),
+        ];
+        let mut watcher = Watcher::new(&dirs).expect("Error watching src, data and po");
+        let vars = runtime::variables(metadata, self.profile, prefix, launch.memory_settings);
+        let binary = prefix.join("bin").join(&metadata.bin);
+        let resourcedir = self.datadir.join("resources");
+        let is_stylesheet = |path: &PathBuf| {
+            path.starts_with(&resourcedir)
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "css" || ext == "scss")
+        };
+
+        let mut app: Option<std::process::Child> = None;
+        loop {
+            // A failed build keeps the last working instance running
+            if self.build(metadata, prefix) {
+                if let Some(mut child) = app.take() {
+                    child.kill().ok();
+                    child.wait().ok();
+                }
+                let mut command = launch.command(&binary, appargs, &vars);
+                app = command.spawn().ok();
+                if app.is_none() {
+                    println!("{} not found!", command.get_program().to_string_lossy());
+                }
+            }
+
+            loop {
+                println!("\x1b[1;38;5;29m    Watching\x1b[0m src, data and po for changes");
+                let changed = watcher.wait(Duration::from_millis(300));
+                for path in &changed {
+                    println!("\x1b[1;38;5;29m     Changed\x1b[0m {}", path.display());
+                }
+                if self.profile != "debug" || !changed.iter().all(is_stylesheet) {
+                    break;
+                }
+                let (status, name) = Step::Stylesheets.banner();
+                println!("\x1b[1;38;5;29m{:>12}\x1b[0m {}", status, name);
+                self.commands(metadata)
+                    .compile_stylesheets(&scss::sources(&resourcedir));
+            }
+        }
+    }
+
+    // Extract translatable strings into po/{domain}.pot
+    pub fn pot(&self, metadata: &Metadata) -> Option<PathBuf> {
+        self.commands(metadata).generate_pot()
+    }
+
+    // Merge po/{domain}.pot into the languages in LINGUAS
+    pub fn update_po(&self, metadata: &Metadata) -> Option<()> {
+        self.commands(metadata).update_po()
+    }
+
+    // Translation progress of every .po file, as "text" or "json"
+    pub fn i18n_stats(&self, metadata: &Metadata, format: &str) -> Option<String> {
+        self.commands(metadata).i18n_stats(format)
+    }
+
+    pub fn create_project(id: &str, name: &str, bin: &str) -> Option<()> {
+        Command::new("cargo")
+            .args(["new", "--bin", bin])
+            .status()
+            .ok()?;
+
+        let toml = PathBuf::from(bin).join("Cargo.toml");
+
+        let template = std::fs::read_to_string(&toml).ok()?;
+        std::fs::write(
+            &toml,
+            format!(
+                "{}[dependencies]
+log = \"0.4\"
+gettext-rs = {{ version = \"0.5\", features = [\"gettext-system\"] }}
+
+[dependencies.gtk]
+git = \"https://github.com/gtk-rs/gtk4\"
+package = \"gtk4\"
+
+[dependencies.glib]
+git = \"https://github.com/gtk-rs/glib\"
+features = [\"v2_60\"]
+
+[dependencies.gio]
+git = \"https://github.com/gtk-rs/gio\"
+features = [\"v2_60\"]
+
+[dependencies.gdk]
+git = \"https://github.com/gtk-rs/gdk4\"
+package = \"gdk4\"
+
+[build-dependencies]
+cargo-pkg = \"{}\"
+                \n\n[package.metadata.pkg]\nid = \"{}\"\nname = \"{}\"",
+                &template[..template.len() - 15],
+                env!("CARGO_PKG_VERSION"),
+                id,
+                name
+            ),
+        )
+        .ok()?;
+
+        std::fs::write(
+            PathBuf::from(bin).join("src").join("main.rs"),
+            "use gettextrs::*;
+            extern crate gio;
+extern crate gtk;
+
+use gio::prelude::*;
+use gtk::prelude::*;
+use gtk::ApplicationWindow;
+
+include!(concat!(env!(\"OUT_DIR\"), \"/config.rs\"));
+
+use std::env::args;
+
+fn main() {
+    // Initiialize gtk, gstreamer, and libhandy.
+    gtk::init().expect(\"Failed to initialize gtk!\");
+
+    // Setup language / translations
+    setlocale(LocaleCategory::LcAll, \"\");
+    bindtextdomain(GETTEXT_PACKAGE, LOCALEDIR);
+    textdomain(GETTEXT_PACKAGE);
+
+    // Register resources so we can integrate things like UI files, CSS, and icons
+    register_resources();
+
+    // Set up CSS
+    let provider = gtk::CssProvider::new();
+    load_css(&provider, \"style.css\");
+    gtk::StyleContext::add_provider_for_display(
+        &gdk::Display::get_default().unwrap(),
+        &provider,
+        600,
+    );
+
+    let application =
+        gtk::Application::new(Some(APP_ID), Default::default())
+            .expect(\"Initialization failed...\");
+
+    application.connect_activate(|app| {
+        let builder = gtk::Builder::from_resource(&(GRESOURCE_ID.to_owned() + \"window.ui\"));
+
+        let window: ApplicationWindow = builder.get_object(\"window\").expect(\"Couldn't get window\");
+        window.set_application(Some(app));
+    
+        window.show();
+    });
+
+    application.run(&args().collect::<Vec<_>>());
+}",
+        )
+        .ok()?;
+
+        std::fs::write(
+            PathBuf::from(bin).join("build.rs"),
+            "fn main() {
+    cargo_pkg::buildscript::generate_config().expect(\"Error generating config.rs\");
+}
+",
+        )
+        .ok()?;
+
+        let datadir = PathBuf::from(bin.to_string() + "/data");
+        std::fs::create_dir_all(datadir.join("resources")).ok()?;
+        std::fs::create_dir_all(datadir.join("icons")).ok()?;
+
+        let podir = PathBuf::from(bin.to_string() + "/po");
+        std::fs::create_dir_all(&podir).ok()?;
+
+        std::fs::write(
+            datadir.join(id.to_owned() + ".gschema.xml.in"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<schemalist gettext-domain=\"@GETTEXT_DOMAIN@\">
+    <schema id=\"@APP_ID@\" path=\"/@GRESOURCE_ID@/\">
+    </schema>
+</schemalist>",
+        )
+        .ok()?;
+
+        std::fs::write(
+            datadir.join(id.to_owned() + ".desktop.in"),
+            "[Desktop Entry]
+Name=@APP_NAME@
+Exec=@APP_BINARY@
+Icon=@APP_ID@ // Do not translate
+Terminal=false
+Type=Application
+StartupNotify=true",
+        )
+        .ok()?;
+
+        std::fs::write(
+            datadir.join(id.to_owned() + ".appdata.xml.in"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<component>
+    <id>@APP_ID@</id>
+    <name>@APP_NAME@</name>
+    <summary>A foo-ish bar</summary>
+    <url type=\"homepage\">http://www.example.org</url>
+    <metadata_license>CC0-1.0</metadata_license>
+    <provides>
+        <binary>@APP_BINARY@</binary>
+    </provides>
+    <releases>
+        <release version=\"@APP_VERSION@\"/>
+    </releases>
+    <developer_name>FooBar Team</developer_name>
+</component>",
+        )
+        .ok()?;
+
+        std::fs::write(
+            datadir.join(id.to_owned() + ".gresource.xml.in"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<gresources>
+    <gresource prefix=\"/@GRESOURCE_ID@/\">
+        <file compressed=\"true\" preprocess=\"xml-stripblanks\">window.ui</file>
+        <file compressed=\"true\" alias=\"style.css\">style.css</file>
+    </gresource>
+</gresources>",
+        )
+        .ok()?;
+
+        std::fs::write(
+            datadir.join("resources").join("window.ui"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<interface>
+    <object class=\"GtkApplicationWindow\" id=\"window\">
+    <property name=\"visible\">True</property>
+    <property name=\"can_focus\">False</property>
+    <property name=\"default_width\">600</property>
+    <property name=\"default_height\">400</property>
+    <child type=\"titlebar\">
+        <object class=\"GtkHeaderBar\" id=\"headerbar\">
+        <property name=\"visible\">True</property>
+        <property name=\"can_focus\">False</property>
+        </object>
+    </child>
+    <child>
+        <object class=\"GtkLabel\" id=\"label\">
+        <property name=\"visible\">True</property>
+        <property name=\"can_focus\">False</property>
+        <property name=\"label\" translatable=\"yes\">Hello world!</property>
+        <style>
+            <class name=\"title-header\"/>
+        </style>
+        </object>
+    </child>
+    </object>
+</interface>",
+        )
+        .ok()?;
+
+        std::fs::write(
+            datadir.join("resources").join("style.css"),
+            ".title-header { font-size: 40px }",
+        )
+        .ok()?;
+
+        std::fs::write(podir.join("LINGUAS"), "").ok()?;
+        std::fs::write(
+            podir.join("POTFILES.in"),
+            format!(
+                "src/**/*.rs
+data/{id}.desktop.in
+data/{id}.appdata.xml.in
+data/{id}.gschema.xml.in
+data/resources/**/*.ui
+",
+                id = id
+            ),
+        )
+        .ok()?;
+
+        Some(())
+    }
+}
+
+// $PREFIX and $OUTDIR in a hook's inputs and outputs
+fn expand(path: &str, prefix: &Path, outdir: &Path) -> String {
+    path.replace("$PREFIX", &prefix.to_string_lossy())
+        .replace("$OUTDIR", &outdir.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(name: &str, before: Option<&str>, after: Option<&str>) -> Hook {
+        Hook {
+            name: name.to_string(),
+            command: "true".to_string(),
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        let mut builder = Builder::new(&[], "debug", false);
+        builder.add_hooks(&[
+            hook("zz", None, Some("icons")),
+            hook("aa", None, Some("icons")),
+            hook("pre-schemas", Some("schemas"), None),
+            hook("first", Some("icons"), None),
+            hook("second", Some("icons"), None),
+            hook("last", None, None),
+            hook("typo", None, Some("icon")),
+        ]);
+        let names = builder.steps.iter().map(Step::name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "config-files",
+                "translations",
+                "appdata",
+                "blueprints",
+                "stylesheets",
+                "resources",
+                "first",
+                "second",
+                "icons",
+                "zz",
+                "aa",
+                "pre-schemas",
+                "schemas",
+                "config",
+                "binary",
+                "last",
+                "typo"
+            ]
+        );
+    }
+}
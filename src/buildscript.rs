@@ -0,0 +1,72 @@
+use super::blueprint;
+use super::commands::{write_changed, Commands};
+use super::metadata::Metadata;
+use super::scss;
+use std::{env, path::PathBuf};
+
+// Write the config.rs cargo-pkg generates into OUT_DIR, so the app
+// also builds with plain cargo, cargo test and rust-analyzer.
+// Call it from the app's build.rs:
+//
+//     fn main() {
+//         cargo_pkg::buildscript::generate_config().expect("Error generating config.rs");
+//     }
+//
+// and include it with include!(concat!(env!("OUT_DIR"), "/config.rs"));
+pub fn generate_config() -> Option<()> {
+    let manifestdir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").ok()?);
+    let outdir = PathBuf::from(env::var("OUT_DIR").ok()?);
+    let profile = env::var("PROFILE").ok()?;
+    // Set by cargo-pkg, /usr/local is where it installs by default
+    let prefix = env::var("CARGO_PKG_PREFIX")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/usr/local"));
+
+    let metadata = Metadata::from(manifestdir.join("Cargo.toml").to_str()?)?;
+    let datadir = manifestdir.join("data");
+    let podir = manifestdir.join("po");
+    let dataout = outdir.join("data");
+    // cargo-pkg has compiled stylesheets and the bundle already,
+    // config.rs refers to those so `cargo pkg watch` can update them.
+    let compiled = env::var("CARGO_PKG_OUTDIR").map(PathBuf::from).ok();
+    let commands = Commands {
+        root: &manifestdir,
+        datadir: &datadir,
+        podir: &podir,
+        outdir: compiled.clone().unwrap_or_else(|| dataout.clone()),
+        metadata: &metadata,
+        profile: &profile,
+        min_percent: 0,
+        pseudo_locale: false,
+    };
+
+    // Schemas need their @VARIABLES@ filled in
+    commands.process_config_files(&dataout)?;
+
+    // Without cargo-pkg nothing else compiles them. Debug builds
+    // load stylesheets from disk and a bundle to embed has to exist
+    // before the app compiles.
+    let glibresource = dataout.join(metadata.id.clone() + ".gresource.xml");
+    let resourcedir = datadir.join("resources");
+    if compiled.is_none() && resourcedir.exists() {
+        commands.compile_stylesheets(&scss::sources(&resourcedir))?;
+        if metadata.embed_resources && glibresource.exists() {
+            commands.compile_blueprints(&blueprint::sources(&resourcedir))?;
+            commands.install_glib_resources(&glibresource, &prefix)?;
+        }
+    }
+    write_changed(
+        &outdir.join("config.rs"),
+        &commands.config_rs(&dataout, &prefix)?,
+    )?;
+
+    for path in &["Cargo.toml", "data", "po"] {
+        println!(
+            "cargo:rerun-if-changed={}",
+            manifestdir.join(path).display()
+        );
+    }
+    println!("cargo:rerun-if-env-changed=CARGO_PKG_PREFIX");
+    println!("cargo:rerun-if-env-changed=CARGO_PKG_OUTDIR");
+    Some(())
+}
@@ -1,11 +1,34 @@
+use super::blueprint;
+use super::fingerprint::read_dir_sorted;
+use super::gettext;
+use super::gresource::GResource;
+use super::gschema::{is_schema_file, SchemaList};
 use super::metadata::Metadata;
-use std::{collections::HashMap, env, ffi::OsStr, path::PathBuf, process::Command};
+use super::mo;
+use super::po::Catalog;
+use super::pseudo;
+use super::runtime::json_quote;
+use super::scss;
+use super::settings;
+use super::translate;
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 pub struct Commands<'a> {
+    pub root: &'a PathBuf,
     pub datadir: &'a PathBuf,
     pub podir: &'a PathBuf,
+    // target/{debug/release}/data, or OUT_DIR/data in build.rs
+    pub outdir: PathBuf,
     pub metadata: &'a Metadata,
     pub profile: &'a str,
+    pub min_percent: u32,
+    pub pseudo_locale: bool,
 }
 
 impl<'a> Commands<'a> {
@@ -13,31 +36,18 @@ impl<'a> Commands<'a> {
     // and fill data in templates then move
     // to target/{debug/release}/data directory
     // If file isn't .in move it as it is.
-    pub fn process_config_files(&self, outdir: &PathBuf) -> Option<()> {
+    pub fn process_config_files(&self, outdir: &Path) -> Option<()> {
         if self.datadir.exists() {
-            let gresource_id = &self.metadata.id.replace(".", "/");
+            std::fs::create_dir_all(outdir).ok()?;
 
-            let mut variables = HashMap::new();
-            variables.insert("@APP_ID@", &self.metadata.id);
-            variables.insert("@APP_BINARY@", &self.metadata.bin);
-            variables.insert("@APP_NAME@", &self.metadata.name);
-            variables.insert("@APP_VERSION@", &self.metadata.version);
-            variables.insert("@GRESOURCE_ID@", &gresource_id);
-            variables.insert("@GETTEXT_DOMAIN@", &self.metadata.bin);
-
-            std::fs::create_dir_all(&outdir).ok()?;
-
-            for file in std::fs::read_dir(&self.datadir).ok()? {
+            for file in std::fs::read_dir(self.datadir).ok()? {
                 let path = file.ok()?.path();
                 if path.extension() == Some(OsStr::new("in")) {
-                    let mut data = std::fs::read_to_string(&path).ok()?;
-                    for (key, value) in variables.iter() {
-                        data = data.replace(key, &value);
-                    }
-                    let output = outdir.join(&path.file_stem()?);
-                    std::fs::write(&output.as_path(), data).unwrap();
+                    let data = self.substitute(&std::fs::read_to_string(&path).ok()?);
+                    let output = outdir.join(path.file_stem()?);
+                    std::fs::write(output.as_path(), data).ok()?;
                 } else if path.is_file() {
-                    std::fs::copy(&path, outdir.join(&path.file_name()?)).ok()?;
+                    std::fs::copy(&path, outdir.join(path.file_name()?)).ok()?;
                 }
             }
         }
@@ -45,45 +55,245 @@ impl<'a> Commands<'a> {
         Some(())
     } //------------------------------------------------------
 
-    // Process and compile po files
-    // and move to share/locale/{lang}/LC_MESSAGES/{lang}.mo
-    pub fn install_langauge_files(&self, prefix: &PathBuf) -> Option<()> {
+    // Fill @APP_ID@ and the like into the text of an .in file
+    pub fn substitute(&self, text: &str) -> String {
+        let gresource_id = &self.metadata.id.replace(".", "/");
+
+        let mut variables = HashMap::new();
+        variables.insert("@APP_ID@", &self.metadata.id);
+        variables.insert("@APP_BINARY@", &self.metadata.bin);
+        variables.insert("@APP_NAME@", &self.metadata.name);
+        variables.insert("@APP_VERSION@", &self.metadata.version);
+        variables.insert("@GRESOURCE_ID@", gresource_id);
+        variables.insert("@GETTEXT_DOMAIN@", &self.metadata.gettext_domain);
+
+        let mut text = text.to_string();
+        for (key, value) in variables.iter() {
+            text = text.replace(key, value);
+        }
+        text
+    }
+
+    // Process and compile po files of the languages in LINGUAS
+    // and move to share/locale/{lang}/LC_MESSAGES/{domain}.mo,
+    // with --pseudo-locale en@pseudo generated from the .pot too.
+    pub fn install_langauge_files(&self, prefix: &Path) -> Option<()> {
         if self.podir.exists() {
+            gettext::check_linguas(self.podir);
+
             let modir = prefix.join("share/locale");
             std::fs::create_dir_all(&modir).ok()?;
-            for file in std::fs::read_dir(&self.podir.as_path()).ok()? {
-                let path = file.ok()?.path();
+            for path in gettext::catalogs(self.podir) {
+                let name = path.file_stem()?.to_str()?;
+                let modir = modir.join(name).join("LC_MESSAGES");
+                let mo = modir.join(self.metadata.gettext_domain.clone() + ".mo");
 
-                if path.extension() == Some(OsStr::new("po")) {
-                    let name = path.file_stem()?.to_str()?;
-                    let modir = modir.join(&name).join("LC_MESSAGES");
-                    std::fs::create_dir_all(&modir).ok()?;
-
-                    let mut mo = modir.join(&name);
-                    mo.set_extension("mo");
+                if let Some(percent) = self.below_min_percent(&path) {
+                    println!(
+                        "\x1b[1;33mwarning\x1b[0m: {} is {:.1}% translated, below --min-percent {}, not installing it",
+                        path.display(),
+                        percent,
+                        self.min_percent
+                    );
+                    // Left over from a build with a lower threshold
+                    std::fs::remove_file(&mo).ok();
+                    continue;
+                }
+                std::fs::create_dir_all(&modir).ok()?;
 
-                    Command::new("msgfmt")
-                        .args(&[path.to_str()?, "-o"])
+                if self.metadata.use_msgfmt {
+                    isexists("msgfmt")?;
+                    let status = Command::new("msgfmt")
+                        .args(["--check-format", path.to_str()?, "-o"])
                         .arg(&mo)
                         .status()
                         .ok()?;
+                    if !status.success() {
+                        return None;
+                    }
+                } else {
+                    mo::compile(&path, &mo)?;
                 }
             }
+
+            if self.pseudo_locale {
+                let pot = self
+                    .podir
+                    .join(self.metadata.gettext_domain.clone() + ".pot");
+                if !pot.exists() {
+                    println!(
+                        "\x1b[1;31merror\x1b[0m: {} doesn't exist, run `cargo pkg pot` first",
+                        pot.display()
+                    );
+                    return None;
+                }
+                let modir = modir.join(pseudo::LOCALE).join("LC_MESSAGES");
+                std::fs::create_dir_all(&modir).ok()?;
+                let catalog = pseudo::catalog(&Catalog::from(&pot)?);
+                std::fs::write(
+                    modir.join(self.metadata.gettext_domain.clone() + ".mo"),
+                    mo::write(&catalog),
+                )
+                .ok()?;
+            }
         }
 
         Some(())
     } //------------------------------------------------------
 
+    // Percentage of po if it's too incomplete to install
+    pub fn below_min_percent(&self, po: &Path) -> Option<f64> {
+        if self.min_percent == 0 {
+            return None;
+        }
+        let percent = Catalog::from(po)?.stats().percent();
+        if percent < self.min_percent as f64 {
+            Some(percent)
+        } else {
+            None
+        }
+    }
+
+    // Translated, fuzzy and untranslated strings of every .po file as
+    // text or json, languages which won't be installed are marked.
+    pub fn i18n_stats(&self, format: &str) -> Option<String> {
+        let linguas = gettext::catalogs(self.podir);
+        let mut languages = Vec::new();
+        for (language, stats) in gettext::stats(self.podir) {
+            let listed = linguas
+                .iter()
+                .any(|path| path.file_stem() == Some(OsStr::new(&language)));
+            let complete = stats.percent() >= self.min_percent as f64;
+            languages.push((language, stats, listed, complete));
+        }
+
+        match format {
+            "text" => Some(
+                languages
+                    .iter()
+                    .map(|(language, stats, listed, complete)| {
+                        let mut line = format!(
+                            "\x1b[1;38;5;29m{:>12}\x1b[0m {} translated, {} fuzzy, {} untranslated ({:.1}%)",
+                            language,
+                            stats.translated,
+                            stats.fuzzy,
+                            stats.untranslated,
+                            stats.percent()
+                        );
+                        if !listed {
+                            line.push_str(", not in LINGUAS");
+                        } else if !complete {
+                            line.push_str(&format!(", below {}%", self.min_percent));
+                        }
+                        line + "\n"
+                    })
+                    .collect(),
+            ),
+            "json" => Some(format!(
+                "[\n{}\n]\n",
+                languages
+                    .iter()
+                    .map(|(language, stats, listed, complete)| {
+                        format!(
+                            "  {{\"language\": {}, \"translated\": {}, \"fuzzy\": {}, \"untranslated\": {}, \"total\": {}, \"percent\": {:.1}, \"installed\": {}}}",
+                            json_quote(language),
+                            stats.translated,
+                            stats.fuzzy,
+                            stats.untranslated,
+                            stats.total(),
+                            stats.percent(),
+                            *listed && *complete
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",\n")
+            )),
+            _ => None,
+        }
+    }
+
+    // Extract the translatable strings of the files
+    // in POTFILES.in into po/{domain}.pot for translators.
+    pub fn generate_pot(&self) -> Option<PathBuf> {
+        let mut files = Vec::new();
+        for path in gettext::potfiles(self.podir)? {
+            let mut text = std::fs::read_to_string(self.root.join(&path)).ok()?;
+            // Strings of .in files are looked up
+            // after @APP_NAME@ and the like are filled in.
+            if path.extension() == Some(OsStr::new("in")) {
+                text = self.substitute(&text);
+            }
+            files.push((path, text));
+        }
+
+        let pot = self
+            .podir
+            .join(self.metadata.gettext_domain.clone() + ".pot");
+        gettext::extract(
+            &files,
+            &self.metadata.targetdir.join("pot"),
+            &absolute(&pot),
+            &self.metadata.bin,
+            &self.metadata.version,
+        )?;
+        Some(pot)
+    }
+
+    // Merge po/{domain}.pot into the .po file of every language in
+    // LINGUAS, creating missing ones, and print what changed.
+    pub fn update_po(&self) -> Option<()> {
+        let pot = self
+            .podir
+            .join(self.metadata.gettext_domain.clone() + ".pot");
+        if !pot.exists() {
+            println!(
+                "\x1b[1;31merror\x1b[0m: {} doesn't exist, run `cargo pkg pot` first",
+                pot.display()
+            );
+            return None;
+        }
+        let linguas = match gettext::linguas(self.podir) {
+            Some(linguas) => linguas,
+            None => {
+                println!(
+                    "\x1b[1;31merror\x1b[0m: {} doesn't exist",
+                    self.podir.join("LINGUAS").display()
+                );
+                return None;
+            }
+        };
+
+        for language in linguas {
+            let po = self.podir.join(language.clone() + ".po");
+            let merged = gettext::update_po(&po, &pot, &language)?;
+            println!(
+                "\x1b[1;38;5;29m{:>12}\x1b[0m {}: {} new, {} fuzzy, {} obsolete",
+                if merged.created { "Created" } else { "Updated" },
+                po.display(),
+                merged.new,
+                merged.fuzzy,
+                merged.obsolete
+            );
+        }
+        Some(())
+    }
+
     // Translate .appdata.xml and .desktop and
     // install to share/appdata and share/applications
     pub fn install_appdata_and_desktop(
         &self,
-        appdata: &PathBuf,
-        desktop: &PathBuf,
-        prefix: &PathBuf,
+        appdata: &Path,
+        desktop: &Path,
+        prefix: &Path,
     ) -> Option<()> {
+        if !self.podir.exists() && (appdata.exists() || desktop.exists()) {
+            println!(
+                "\x1b[1;33mwarning\x1b[0m: {} doesn't exist, installing appdata and desktop files untranslated",
+                self.podir.display()
+            );
+        }
         for file in &[appdata, desktop] {
-            let path = file.as_path();
+            let path = *file;
             if path.exists() {
                 let ttype = "--".to_owned() + path.extension()?.to_str()?;
 
@@ -96,44 +306,143 @@ impl<'a> Commands<'a> {
                 let npath = ndir.join(file.file_name()?);
                 std::fs::create_dir_all(&ndir).ok()?;
 
-                if self.podir.exists() {
+                if self.metadata.use_msgfmt && self.podir.exists() {
+                    isexists("msgfmt")?;
+                    // Instead of po/LINGUAS, which may list missing files
+                    let linguas = gettext::catalogs(self.podir)
+                        .iter()
+                        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                        .collect::<Vec<_>>();
                     Command::new("msgfmt")
+                        .env("LINGUAS", linguas.join(" "))
                         .arg(ttype)
-                        .args(&["--template", &path.to_str()?])
-                        .args(&["-d", &self.podir.as_path().to_str()?])
-                        .args(&["-o", &npath.as_path().to_str()?])
+                        .args(["--template", path.to_str()?])
+                        .args(["-d", self.podir.as_path().to_str()?])
+                        .args(["-o", npath.as_path().to_str()?])
                         .status()
                         .ok()?;
                 } else {
-                    std::fs::copy(&path, &npath.as_path()).ok()?;
+                    let catalogs = gettext::load(self.podir);
+                    let text = std::fs::read_to_string(path).ok()?;
+                    let text = if ttype == "--desktop" {
+                        translate::desktop(&text, &catalogs)
+                    } else {
+                        match translate::appdata(&text, &catalogs) {
+                            Ok(text) => text,
+                            Err(err) => {
+                                println!("\x1b[1;31merror\x1b[0m: {}: {}", path.display(), err);
+                                return None;
+                            }
+                        }
+                    };
+                    std::fs::write(&npath, text).ok()?;
                 }
             }
         }
         Some(())
     } //------------------------------------------------------
 
+    // Where the compiled bundle goes, share/{app_id}/{app_id}.gresource
+    // or target/{debug/release}/data if it's embedded into the binary.
+    pub fn gresource_target(&self, prefix: &Path) -> PathBuf {
+        let dir = if self.metadata.embed_resources {
+            self.outdir.clone()
+        } else {
+            prefix.join("share").join(&self.metadata.id)
+        };
+        dir.join(self.metadata.id.clone() + ".gresource")
+    }
+
+    // Where .blp files from data/resources are compiled to
+    pub fn blueprint_dir(&self) -> PathBuf {
+        self.outdir.join("blueprints")
+    }
+
+    // Compile Blueprint files to .ui for install_glib_resources
+    pub fn compile_blueprints(&self, sources: &[PathBuf]) -> Option<()> {
+        blueprint::compile(
+            &self.datadir.join("resources"),
+            &self.blueprint_dir(),
+            sources,
+        )
+    }
+
+    // Where .scss files from data/resources are compiled to
+    pub fn stylesheet_dir(&self) -> PathBuf {
+        self.outdir.join("stylesheets")
+    }
+
+    // Compile SCSS to CSS, with dark and high contrast
+    // variants for stylesheets reading $variant.
+    pub fn compile_stylesheets(&self, sources: &[PathBuf]) -> Option<()> {
+        scss::compile(
+            &self.datadir.join("resources"),
+            &self.stylesheet_dir(),
+            sources,
+        )
+    }
+
+    // Make .blp and .scss entries of the bundle refer to what they compile to
+    pub fn rewrite_compiled(&self, gresource: &mut GResource) {
+        let resourcedir = self.datadir.join("resources");
+        blueprint::rewrite(gresource, &resourcedir, &self.blueprint_dir());
+        scss::rewrite(gresource, &resourcedir, &self.stylesheet_dir());
+    }
+
     //Compile glib resources and install it to
     // share/{app_id}/{app_id}.
-    pub fn install_glib_resources(&self, glibresource: &PathBuf, prefix: &PathBuf) -> Option<()> {
+    pub fn install_glib_resources(&self, glibresource: &Path, prefix: &Path) -> Option<()> {
         let resourcedir = self.datadir.join("resources");
         if glibresource.exists() && resourcedir.exists() {
-            let installdir = prefix.join("share").join(&self.metadata.id);
-            std::fs::create_dir_all(&installdir).ok()?;
-
-            Command::new("glib-compile-resources")
-                .args(&[
-                    glibresource.to_str()?,
-                    "--sourcedir",
-                    resourcedir.to_str()?,
-                    "--internal",
-                    "--generate",
-                    "--target",
-                    installdir
-                        .join(self.metadata.id.clone() + ".gresource")
-                        .to_str()?,
-                ])
-                .status()
-                .ok()?;
+            // Catch missing files before compiling
+            let mut gresource = GResource::from(glibresource)?;
+            gresource.check(&resourcedir)?;
+
+            // Bundle what .blp and .scss files compiled to instead
+            self.rewrite_compiled(&mut gresource);
+            let rewritten = gresource
+                .files
+                .iter()
+                .any(|file| Path::new(&file.path).is_absolute());
+            for file in &gresource.files {
+                let path = resourcedir.join(&file.path);
+                if !path.exists() {
+                    println!(
+                        "\x1b[1;31merror\x1b[0m: {} hasn't been compiled",
+                        path.display()
+                    );
+                    return None;
+                }
+            }
+
+            let target = self.gresource_target(prefix);
+            std::fs::create_dir_all(target.parent()?).ok()?;
+
+            if gresource.is_native() {
+                std::fs::write(&target, gresource.compile(&resourcedir)?).ok()?;
+            } else {
+                // Fallback for preprocessors like to-pixdata
+                isexists("glib-compile-resources")?;
+                let glibresource = if rewritten {
+                    let xml = glibresource.with_extension("compiled.xml");
+                    std::fs::write(&xml, gresource.to_xml()).ok()?;
+                    xml
+                } else {
+                    glibresource.to_path_buf()
+                };
+                Command::new("glib-compile-resources")
+                    .args([
+                        glibresource.to_str()?,
+                        "--sourcedir",
+                        resourcedir.to_str()?,
+                        "--internal",
+                        "--generate",
+                        "--target",
+                        target.to_str()?,
+                    ])
+                    .status()
+                    .ok()?;
+            }
         } //-------------------------------------------
 
         Some(())
@@ -142,7 +451,7 @@ impl<'a> Commands<'a> {
     // Install scaleable and symbolic icons
     // to share/icons/{scalable/symbolic}/apps
     // If they are exists in data/icons directory
-    pub fn install_icon_files(&self, prefix: &PathBuf) -> Option<()> {
+    pub fn install_icon_files(&self, prefix: &Path) -> Option<()> {
         let scalable = self
             .datadir
             .join("icons")
@@ -169,22 +478,82 @@ impl<'a> Commands<'a> {
         Some(())
     }
 
-    // Install gschema to share/glib-2.0/schema
-    pub fn install_glib_schemas(&self, gschema: &PathBuf, prefix: &PathBuf) -> Option<()> {
-        if gschema.exists() {
-            let installdir = prefix.join("share/glib-2.0/schemas");
-            std::fs::create_dir_all(&installdir).ok()?;
-            let ngschema = installdir.join(gschema.file_name()?);
-            std::fs::copy(&gschema.as_path(), &ngschema.as_path()).ok()?;
+    // Validate gschemas and enums and install them to share/glib-2.0/schemas
+    // then compile every schema there into gschemas.compiled
+    pub fn install_glib_schemas(&self, gschemas: &[PathBuf], prefix: &Path) -> Option<()> {
+        if gschemas.is_empty() {
+            return Some(());
+        }
+
+        SchemaList::from(gschemas)?.check()?;
+
+        let installdir = prefix.join("share/glib-2.0/schemas");
+        std::fs::create_dir_all(&installdir).ok()?;
+        for gschema in gschemas {
+            std::fs::copy(gschema, installdir.join(gschema.file_name()?)).ok()?;
+        }
+
+        let native = self.metadata.native_schemas
+            || Command::new("glib-compile-schemas")
+                .arg("--version")
+                .output()
+                .is_err();
+        if native {
+            // Schemas of other applications may be installed here too
+            let schemas = SchemaList::from_dir(&installdir);
+            std::fs::write(installdir.join("gschemas.compiled"), schemas.compile()?).ok()?;
+        } else {
             Command::new("glib-compile-schemas")
-                .args(&[installdir.as_path().to_str()?])
+                .args([installdir.as_path().to_str()?])
                 .status()
                 .ok()?;
         }
         Some(())
     }
 
-    pub fn generate_config_rs(&self, outdir: &PathBuf, prefix: &PathBuf) -> Option<()> {
+    // Write config.rs to outdir and export its path as CONFIG_PATH
+    // for projects doing include!(env!("CONFIG_PATH")).
+    pub fn generate_config_rs(&self, outdir: &Path, prefix: &Path) -> Option<()> {
+        let config = self.config_rs(outdir, prefix)?;
+
+        std::fs::create_dir_all(outdir).ok()?;
+        let dest_path = &outdir.join("config.rs");
+        write_changed(dest_path, &config)?;
+        env::set_var(
+            "CONFIG_PATH",
+            std::fs::canonicalize(dest_path).ok()?.as_path().to_str()?,
+        );
+
+        Some(())
+    }
+
+    // Stylesheets of the bundle compiled from SCSS, by their name
+    // below GRESOURCE_ID, e.g. ("style.css", target/debug/data/stylesheets/style.css)
+    fn compiled_stylesheets(&self, outdir: &Path) -> Vec<(String, String)> {
+        let glibresource = outdir.join(self.metadata.id.clone() + ".gresource.xml");
+        let mut gresource = match GResource::from(&glibresource) {
+            Some(gresource) => gresource,
+            None => return Vec::new(),
+        };
+        scss::rewrite(
+            &mut gresource,
+            &self.datadir.join("resources"),
+            &self.stylesheet_dir(),
+        );
+        let prefix = ["/", &self.metadata.id.replace(".", "/"), "/"].concat();
+        gresource
+            .files
+            .iter()
+            .filter(|file| Path::new(&file.path).is_absolute())
+            .filter_map(|file| {
+                let name = file.resource_path().strip_prefix(&prefix)?.to_string();
+                Some((name, file.path.clone()))
+            })
+            .collect()
+    }
+
+    // Contents of config.rs, outdir holds the processed data files
+    pub fn config_rs(&self, outdir: &Path, prefix: &Path) -> Option<String> {
         let mut config = format!(
             "pub static APP_ID: &str = \"{}\";
         pub static APP_NAME: &str = \"{}\";
@@ -196,64 +565,162 @@ impl<'a> Commands<'a> {
             &self.metadata.name,
             self.profile,
             &self.metadata.version,
-            &self.metadata.bin,
+            &self.metadata.gettext_domain,
             ["/", &self.metadata.id.replace(".", "/"), "/"].concat()
         )
         .to_owned();
 
-        let pkgdatadir = &prefix.join("share").join(&self.metadata.id);
-        if pkgdatadir.exists() {
-            config.push_str(&format!(
-                "\npub static PKGDATADIR: &str = \"{}\";",
-                std::fs::canonicalize(pkgdatadir).ok()?.as_path().to_str()?
-            ));
+        // Emitted even before anything is installed there,
+        // so the app always compiles.
+        let pkgdatadir = absolute(&prefix.join("share").join(&self.metadata.id));
+        config.push_str(&format!(
+            "\npub static PKGDATADIR: &str = \"{}\";",
+            pkgdatadir.to_str()?
+        ));
+
+        let localedir = absolute(&prefix.join("share").join("locale"));
+        config.push_str(&format!(
+            "\npub static LOCALEDIR: &str = \"{}\";",
+            localedir.to_str()?
+        ));
+
+        // Register resources either from the installed
+        // bundle or from one built into the binary.
+        // An installed bundle may not be there yet,
+        // but one to embed has to exist at build time.
+        let gresource = absolute(&self.gresource_target(prefix));
+        let resourcedir = self.datadir.join("resources");
+        if resourcedir.exists() && (gresource.exists() || !self.metadata.embed_resources) {
+            if self.metadata.embed_resources {
+                config.push_str(&format!(
+                    "\npub static RESOURCES: &[u8] = include_bytes!(\"{}\");
+        #[allow(dead_code)]
+        pub fn register_resources() {{
+            let bytes = glib::Bytes::from_static(RESOURCES);
+            let res = gio::Resource::from_data(&bytes).expect(\"Could not load resources\");
+            gio::resources_register(&res);
+        }}",
+                    gresource.to_str()?
+                ));
+            } else {
+                config.push_str(&format!(
+                    "\n#[allow(dead_code)]
+        pub fn register_resources() {{
+            let res = gio::Resource::load(\"{}\").expect(\"Could not load resources\");
+            gio::resources_register(&res);
+        }}",
+                    gresource.to_str()?
+                ));
+            }
+
+            // Debug builds load stylesheets from data/resources, or from
+            // where SCSS compiled to, and reload them on save,
+            // release ones from the bundle.
+            if self.profile == "debug" && resourcedir.exists() {
+                let stylesheets = self
+                    .compiled_stylesheets(outdir)
+                    .iter()
+                    .map(|(name, path)| format!("({:?}, {:?})", name, path))
+                    .collect::<Vec<_>>();
+                config.push_str(&format!(
+                    "\npub static RESOURCESDIR: &str = \"{}\";
+        pub static STYLESHEETS: &[(&str, &str)] = &[{}];
+        #[allow(dead_code)]
+        pub fn load_css(provider: &gtk::CssProvider, name: &str) {{
+            let path = match STYLESHEETS.iter().find(|(stylesheet, _)| *stylesheet == name) {{
+                Some((_, path)) => std::path::PathBuf::from(path),
+                None => std::path::Path::new(RESOURCESDIR).join(name),
+            }};
+            let file = gio::File::new_for_path(path);
+            provider.load_from_file(&file);
+
+            let monitor = file
+                .monitor_file(gio::FileMonitorFlags::NONE, gio::NONE_CANCELLABLE)
+                .expect(\"Could not watch stylesheet\");
+            let provider = provider.clone();
+            monitor.connect_changed(move |_, file, _, event| {{
+                if event == gio::FileMonitorEvent::ChangesDoneHint {{
+                    provider.load_from_file(file);
+                }}
+            }});
+            // Lives as long as the app
+            std::mem::forget(monitor);
+        }}",
+                    std::fs::canonicalize(resourcedir).ok()?.to_str()?,
+                    stylesheets.join(", ")
+                ));
+            } else {
+                config.push_str(
+                    "\n#[allow(dead_code)]
+        pub fn load_css(provider: &gtk::CssProvider, name: &str) {
+            provider.load_from_resource(&(GRESOURCE_ID.to_owned() + name));
+        }",
+                );
+            }
         }
 
-        let localedir = &prefix.join("share").join("locale");
-        if localedir.exists() {
-            config.push_str(&format!(
-                "\npub static LOCALEDIR: &str = \"{}\";",
-                std::fs::canonicalize(localedir).ok()?.as_path().to_str()?
-            ));
+        // Typed accessors for the app's GSettings schemas
+        let gschemas = read_dir_sorted(outdir)
+            .into_iter()
+            .filter(|path| is_schema_file(path))
+            .collect::<Vec<_>>();
+        if !gschemas.is_empty() {
+            let schemas = SchemaList::from(&gschemas)?;
+            config.push_str(&settings::generate(&schemas, &self.metadata.id));
         }
-        // Generate config.rs
-        std::fs::create_dir_all(&outdir).ok()?;
-        let dest_path = &outdir.join("config.rs");
-        std::fs::write(&dest_path.as_path(), &config).ok()?;
-        env::set_var(
-            "CONFIG_PATH",
-            std::fs::canonicalize(dest_path).ok()?.as_path().to_str()?,
-        );
 
-        Some(())
+        Some(config)
     }
 
     // Install binary
     pub fn install_binary(
         &self,
         buildflags: &[String],
-        prefix: &PathBuf,
+        prefix: &Path,
     ) -> Option<std::process::ExitStatus> {
-        let output;
-        if buildflags.len() == 0 {
-            output = Command::new("cargo")
-                .args(&["install", "--force"])
-                .args(&["--path", ".", "--root"])
-                .arg(prefix.to_str()?)
-                .status()
-                .ok()?;
-        } else {
-            output = Command::new("cargo")
-                .args(&["install", "--force"])
-                .args(buildflags)
-                .args(&["--path", ".", "--root"])
-                .arg(prefix.to_str()?)
-                .status()
-                .ok()?;
-        }
+        let output = Command::new("cargo")
+            .args(["install", "--force"])
+            .args(buildflags)
+            .arg("--path")
+            .arg(self.root)
+            .arg("--root")
+            .arg(prefix.to_str()?)
+            // For buildscript::generate_config in the app's build.rs
+            .env("CARGO_PKG_PREFIX", absolute(prefix))
+            .env("CARGO_PKG_OUTDIR", absolute(&self.outdir))
+            .status()
+            .ok()?;
         std::fs::remove_file(prefix.join(".crates2.json").as_path()).ok()?;
         std::fs::remove_file(prefix.join(".crates.toml").as_path()).ok()?;
 
         Some(output)
     }
 }
+
+// Whether program can be run, "x not found!" is printed if it can't
+pub fn isexists(program: &str) -> Option<()> {
+    if Command::new(program).output().is_ok() {
+        Some(())
+    } else {
+        println!("\x1b[1;31merror\x1b[0m: {} not found!", program);
+        None
+    }
+}
+
+// Keep mtime untouched if nothing changed,
+// otherwise cargo rebuilds the binary every time.
+pub fn write_changed(path: &Path, contents: &str) -> Option<()> {
+    if std::fs::read_to_string(path).ok().as_deref() != Some(contents) {
+        std::fs::write(path, contents).ok()?;
+    }
+    Some(())
+}
+
+// Relative paths would break once the app changes directory
+pub fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| {
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    })
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// Fingerprints of every build step, stored in
+// target/{debug/release}/data/.fingerprints
+// A step is fresh when the hash of its inputs and
+// outputs matches the one recorded after its last run.
+pub struct Fingerprints {
+    path: PathBuf,
+    entries: HashMap<String, u64>,
+    force: bool,
+}
+
+impl Fingerprints {
+    pub fn load(outdir: &Path, force: bool) -> Self {
+        let path = outdir.join(".fingerprints");
+        let entries = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, ' ');
+                let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+                Some((parts.next()?.to_string(), hash))
+            })
+            .collect();
+
+        Fingerprints {
+            path,
+            entries,
+            force,
+        }
+    }
+
+    pub fn is_fresh(&self, step: &str, hash: u64) -> bool {
+        !self.force && self.entries.get(step) == Some(&hash)
+    }
+
+    pub fn update(&mut self, step: &str, hash: u64) -> Option<()> {
+        self.entries.insert(step.to_string(), hash);
+
+        let mut steps = self.entries.iter().collect::<Vec<_>>();
+        steps.sort();
+        let data = steps
+            .iter()
+            .map(|(step, hash)| format!("{:016x} {}\n", hash, step))
+            .collect::<String>();
+        std::fs::write(&self.path, data).ok()
+    }
+}
+
+// FNV-1a hasher, unlike DefaultHasher its output
+// is stable between runs and toolchains.
+#[derive(Clone)]
+pub struct Hasher(u64);
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher {
+    pub fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        self
+    }
+
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        self.write(&(value.len() as u64).to_le_bytes())
+            .write(value.as_bytes())
+    }
+
+    // Hash path and contents of an input file,
+    // directories are walked recursively.
+    pub fn input(&mut self, path: &Path) -> &mut Self {
+        self.str(&path.to_string_lossy());
+        if path.is_dir() {
+            for entry in read_dir_sorted(path) {
+                self.input(&entry);
+            }
+        } else if let Ok(data) = std::fs::read(path) {
+            self.write(&(data.len() as u64).to_le_bytes()).write(&data);
+        } else {
+            self.str("<missing>");
+        }
+        self
+    }
+
+    // Outputs can be large (e.g. the binary),
+    // so only size and modification time are hashed.
+    pub fn output(&mut self, path: &Path) -> &mut Self {
+        self.str(&path.to_string_lossy());
+        match std::fs::metadata(path) {
+            Ok(meta) => {
+                let mtime = meta
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|time| time.as_nanos())
+                    .unwrap_or_default();
+                self.write(&meta.len().to_le_bytes())
+                    .write(&mtime.to_le_bytes())
+            }
+            Err(_) => self.str("<missing>"),
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Entries of a directory in a stable order,
+// empty if the directory doesn't exist.
+pub fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut entries = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
@@ -0,0 +1,508 @@
+use super::commands::isexists;
+use super::fingerprint::read_dir_sorted;
+use super::po::{Catalog, Stats};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// gettext-rs functions and the i18n helpers apps build on them,
+// macros like i18n!() are matched by the same names with a !.
+const KEYWORDS: &[&str] = &[
+    "gettext",
+    "dgettext:2",
+    "dcgettext:2",
+    "ngettext:1,2",
+    "dngettext:2,3",
+    "dcngettext:2,3",
+    "pgettext:1c,2",
+    "npgettext:1c,2,3",
+    "i18n",
+    "i18n_f",
+    "i18n_k",
+    "ni18n:1,2",
+    "ni18n_f:1,2",
+    "ni18n_k:1,2",
+    "pi18n:1c,2",
+    "pi18n_f:1c,2",
+    "pi18n_k:1c,2",
+    "npi18n:1c,2,3",
+    "npi18n_f:1c,2,3",
+    "npi18n_k:1c,2,3",
+];
+
+// Blueprint marks strings with _() and C_()
+const BLUEPRINT_KEYWORDS: &[&str] = &["_", "C_:1c,2"];
+
+// Files listed in POTFILES.in, one path or glob per line
+pub fn potfiles(podir: &Path) -> Option<Vec<PathBuf>> {
+    let potfiles = podir.join("POTFILES.in");
+    let text = match std::fs::read_to_string(&potfiles) {
+        Ok(text) => text,
+        Err(_) => {
+            println!(
+                "\x1b[1;31merror\x1b[0m: {} doesn't exist",
+                potfiles.display()
+            );
+            return None;
+        }
+    };
+
+    // Paths are relative to the crate, po's parent
+    let root = podir.parent().unwrap_or_else(|| Path::new(""));
+    let mut files = Vec::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        // intltool's [type: gettext/glade] prefix
+        let line = match line.strip_prefix('[') {
+            Some(rest) => rest.split_once(']').map_or("", |(_, path)| path.trim()),
+            None => line,
+        };
+        if line.is_empty() {
+            continue;
+        }
+
+        let components = line.split('/').collect::<Vec<_>>();
+        let matched = glob(root, &components)
+            .into_iter()
+            .filter_map(|path| Some(path.strip_prefix(root).ok()?.to_path_buf()))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            println!(
+                "\x1b[1;33mwarning\x1b[0m: {} in {} matches no files",
+                line,
+                potfiles.display()
+            );
+        }
+        for path in matched {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Some(files)
+}
+
+// Files under dir matching components, which
+// can use *, ? and ** for any number of directories.
+fn glob(dir: &Path, components: &[&str]) -> Vec<PathBuf> {
+    let entries = || {
+        let readdir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        read_dir_sorted(readdir)
+            .into_iter()
+            .filter_map(|path| Some(dir.join(path.file_name()?)))
+            .collect::<Vec<_>>()
+    };
+
+    match components.split_first() {
+        None if dir.is_file() => vec![dir.to_path_buf()],
+        None => Vec::new(),
+        Some((&"**", rest)) => {
+            let mut found = glob(dir, rest);
+            for entry in entries().into_iter().filter(|entry| entry.is_dir()) {
+                found.extend(glob(&entry, components));
+            }
+            found
+        }
+        Some((component, rest)) if component.contains(['*', '?']) => {
+            let pattern = component.chars().collect::<Vec<_>>();
+            entries()
+                .into_iter()
+                .filter(|entry| {
+                    let name = entry.file_name().and_then(OsStr::to_str).unwrap_or("");
+                    matches(&pattern, &name.chars().collect::<Vec<_>>())
+                })
+                .flat_map(|entry| glob(&entry, rest))
+                .collect()
+        }
+        Some((component, rest)) => glob(&dir.join(component), rest),
+    }
+}
+
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+// Run xgettext over files, given as their path relative to the
+// crate and contents, writing the template to output. The files
+// are written to workdir first so references stay relative.
+pub fn extract(
+    files: &[(PathBuf, String)],
+    workdir: &Path,
+    output: &Path,
+    package: &str,
+    version: &str,
+) -> Option<()> {
+    if files.is_empty() {
+        println!("\x1b[1;31merror\x1b[0m: POTFILES.in lists no files");
+        return None;
+    }
+    isexists("xgettext")?;
+    let rust = supports_rust();
+
+    std::fs::remove_dir_all(workdir).ok();
+    let mut groups: Vec<(Vec<String>, Vec<&Path>)> = Vec::new();
+    for (path, text) in files {
+        let (args, text) = match path.extension().and_then(OsStr::to_str) {
+            Some("rs") if rust => (rust_args(true), text.clone()),
+            Some("rs") => (rust_args(false), rust_as_c(text)),
+            Some("blp") => (keyword_args("C", BLUEPRINT_KEYWORDS), text.clone()),
+            _ => (Vec::new(), text.clone()),
+        };
+
+        let copy = workdir.join(path);
+        std::fs::create_dir_all(copy.parent()?).ok()?;
+        std::fs::write(&copy, text).ok()?;
+
+        match groups.iter_mut().find(|(group, _)| *group == args) {
+            Some((_, paths)) => paths.push(path),
+            None => groups.push((args, vec![path])),
+        }
+    }
+
+    std::fs::create_dir_all(output.parent()?).ok()?;
+    std::fs::remove_file(output).ok();
+    for (i, (args, paths)) in groups.iter().enumerate() {
+        let status = Command::new("xgettext")
+            .args([
+                "--from-code=UTF-8",
+                "--add-comments",
+                "--force-po",
+                &format!("--package-name={}", package),
+                &format!("--package-version={}", version),
+            ])
+            .args(args)
+            .args(if i > 0 { Some("--join-existing") } else { None })
+            .arg("--directory")
+            .arg(workdir)
+            .arg("--output")
+            .arg(output)
+            .args(paths)
+            .status()
+            .ok()?;
+        if !status.success() {
+            return None;
+        }
+    }
+
+    Some(())
+}
+
+// xgettext learned Rust in gettext 0.24
+fn supports_rust() -> bool {
+    Command::new("xgettext")
+        .arg("--help")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("Rust"))
+}
+
+fn rust_args(native: bool) -> Vec<String> {
+    if native {
+        let mut keywords = KEYWORDS.to_vec();
+        let macros = KEYWORDS
+            .iter()
+            .map(|keyword| match keyword.split_once(':') {
+                Some((name, args)) => format!("{}!:{}", name, args),
+                None => format!("{}!", keyword),
+            })
+            .collect::<Vec<_>>();
+        keywords.extend(macros.iter().map(String::as_str));
+        keyword_args("Rust", &keywords)
+    } else {
+        keyword_args("C", KEYWORDS)
+    }
+}
+
+fn keyword_args(language: &str, keywords: &[&str]) -> Vec<String> {
+    let mut args = vec![format!("--language={}", language), "--keyword".to_string()];
+    args.extend(
+        keywords
+            .iter()
+            .map(|keyword| format!("--keyword={}", keyword)),
+    );
+    args
+}
+
+// Older xgettext reads Rust as C, which mostly works. Turn i18n!(
+// into i18n (, raw strings into plain ones and drop the quote of
+// lifetimes, line numbers stay the same.
+fn rust_as_c(source: &str) -> String {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(source.len());
+    let mut i = 0;
+    let ident = |i: usize| i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_');
+
+    while i < chars.len() {
+        let c = chars[i];
+        // Comments are copied as they are
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                out.push(chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                out.push(chars[i]);
+                i += 1;
+            }
+            out.push_str("*/");
+            i += 2;
+            continue;
+        }
+
+        // r"..." and r#"..."#
+        if c == 'r' && (i == 0 || !ident(i - 1)) {
+            let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+            if chars.get(i + 1 + hashes) == Some(&'"') {
+                let start = i + 2 + hashes;
+                let mut end = start;
+                while end < chars.len()
+                    && !(chars[end] == '"'
+                        && chars[end + 1..].iter().take_while(|c| **c == '#').count() >= hashes)
+                {
+                    end += 1;
+                }
+                let mut newlines = 0;
+                out.push('"');
+                for &c in &chars[start..end.min(chars.len())] {
+                    match c {
+                        '\\' => out.push_str("\\\\"),
+                        '"' => out.push_str("\\\""),
+                        '\n' => {
+                            out.push_str("\\n");
+                            newlines += 1;
+                        }
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+                out.extend(std::iter::repeat_n('\n', newlines));
+                i = end + 1 + hashes;
+                continue;
+            }
+        }
+
+        match c {
+            // Newlines in strings are escaped and moved after it
+            '"' => {
+                let mut newlines = 0;
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    match chars[i] {
+                        '\\' if chars.get(i + 1) == Some(&'\n') => {
+                            out.push('\\');
+                            out.push('\n');
+                            i += 2;
+                            continue;
+                        }
+                        '\\' => {
+                            out.push('\\');
+                            i += 1;
+                        }
+                        '\n' => {
+                            out.push_str("\\n");
+                            newlines += 1;
+                            i += 1;
+                            continue;
+                        }
+                        _ => (),
+                    }
+                    if i < chars.len() {
+                        out.push(chars[i]);
+                    }
+                    i += 1;
+                }
+                out.push('"');
+                out.extend(std::iter::repeat_n('\n', newlines));
+                i += 1;
+            }
+            // 'a' and '\n' are characters, 'a alone a lifetime
+            '\'' if chars.get(i + 1) != Some(&'\\') && chars.get(i + 2) != Some(&'\'') => {
+                i += 1;
+            }
+            '\'' => {
+                out.push(c);
+                i += 1;
+                while i < chars.len() && chars[i] != '\'' {
+                    if chars[i] == '\\' {
+                        out.push(chars[i]);
+                        i += 1;
+                    }
+                    if i < chars.len() {
+                        out.push(chars[i]);
+                    }
+                    i += 1;
+                }
+                out.push('\'');
+                i += 1;
+            }
+            '!' if i > 0 && ident(i - 1) && chars.get(i + 1) == Some(&'(') => {
+                out.push(' ');
+                i += 1;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// Languages in LINGUAS, separated by whitespace, # starts a comment
+pub fn linguas(podir: &Path) -> Option<Vec<String>> {
+    let text = std::fs::read_to_string(podir.join("LINGUAS")).ok()?;
+    Some(
+        text.lines()
+            .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+// .po files to compile, those of the languages in LINGUAS
+// or without LINGUAS every .po file in podir.
+pub fn catalogs(podir: &Path) -> Vec<PathBuf> {
+    let files = read_dir_sorted(podir)
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("po")));
+    match linguas(podir) {
+        Some(linguas) => files
+            .filter(|path| {
+                let language = path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+                linguas.iter().any(|listed| listed == language)
+            })
+            .collect(),
+        None => files.collect(),
+    }
+}
+
+// Parsed catalogs() by language, those with errors are left out
+pub fn load(podir: &Path) -> Vec<(String, Catalog)> {
+    catalogs(podir)
+        .into_iter()
+        .filter_map(|path| {
+            let language = path.file_stem()?.to_str()?.to_string();
+            Some((language, Catalog::from(&path)?))
+        })
+        .collect()
+}
+
+// Stats of every .po file in podir, listed in LINGUAS or not
+pub fn stats(podir: &Path) -> Vec<(String, Stats)> {
+    read_dir_sorted(podir)
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("po")))
+        .filter_map(|path| {
+            let language = path.file_stem()?.to_str()?.to_string();
+            Some((language, Catalog::from(&path)?.stats()))
+        })
+        .collect()
+}
+
+// Warn about .po files missing from LINGUAS and the other way round
+pub fn check_linguas(podir: &Path) {
+    let linguas = match linguas(podir) {
+        Some(linguas) => linguas,
+        None => return,
+    };
+    for path in read_dir_sorted(podir) {
+        let language = path.file_stem().and_then(OsStr::to_str).unwrap_or("");
+        if path.extension() == Some(OsStr::new("po")) && !linguas.iter().any(|l| l == language) {
+            println!(
+                "\x1b[1;33mwarning\x1b[0m: {} isn't listed in LINGUAS and won't be installed",
+                path.display()
+            );
+        }
+    }
+    for language in &linguas {
+        let po = podir.join(language.clone() + ".po");
+        if !po.exists() {
+            println!(
+                "\x1b[1;33mwarning\x1b[0m: {} is listed in LINGUAS but {} doesn't exist",
+                language,
+                po.display()
+            );
+        }
+    }
+}
+
+// What update_po() changed in a language
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub created: bool,
+    pub new: usize,
+    pub fuzzy: usize,
+    pub obsolete: usize,
+}
+
+// Bring po up to date with pot using msgmerge,
+// missing files are created by msginit.
+pub fn update_po(po: &Path, pot: &Path, language: &str) -> Option<Merged> {
+    let template = Catalog::from(pot)?;
+    let mut merged = Merged::default();
+
+    let before = if po.exists() {
+        isexists("msgmerge")?;
+        let before = Catalog::from(po)?;
+        let status = Command::new("msgmerge")
+            .args(["--update", "--backup=none", "--quiet"])
+            .arg(po)
+            .arg(pot)
+            .status()
+            .ok()?;
+        if !status.success() {
+            return None;
+        }
+        before
+    } else {
+        isexists("msginit")?;
+        let output = Command::new("msginit")
+            .args(["--no-translator", "--input"])
+            .arg(pot)
+            .arg("--output-file")
+            .arg(po)
+            .arg(format!("--locale={}", language))
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            return None;
+        }
+        merged.created = true;
+        Catalog::default()
+    };
+
+    let after = Catalog::from(po)?;
+    merged.new = template
+        .current()
+        .filter(|message| {
+            before
+                .find(message.context.as_deref(), &message.id)
+                .is_none()
+        })
+        .count();
+    merged.fuzzy = after.current().filter(|message| message.is_fuzzy()).count();
+    merged.obsolete = after
+        .messages
+        .iter()
+        .filter(|message| message.obsolete)
+        .count();
+    Some(merged)
+}
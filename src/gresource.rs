@@ -0,0 +1,491 @@
+use super::fingerprint::read_dir_sorted;
+use super::gvdb::HashTable;
+use super::scss::is_partial;
+use flate2::{write::ZlibEncoder, Compression};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// Preprocessors compile() knows, others need glib-compile-resources
+const PREPROCESSORS: &[&str] = &["xml-stripblanks", "json-stripblanks"];
+
+// G_RESOURCE_FLAGS_COMPRESSED
+const FLAG_COMPRESSED: u32 = 1;
+
+// A <file> entry of a <gresource> bundle
+#[derive(Debug, Clone)]
+pub struct ResourceFile {
+    pub prefix: String,
+    // Relative to the source directory
+    pub path: String,
+    pub alias: Option<String>,
+    pub compressed: bool,
+    pub preprocess: Vec<String>,
+}
+
+impl ResourceFile {
+    // Path of the resource inside the bundle, e.g. /io/foo/Bar/style.css
+    pub fn resource_path(&self) -> String {
+        let name = self.alias.as_ref().unwrap_or(&self.path);
+        let mut key = String::new();
+        for part in ["/", &self.prefix, "/", name].iter() {
+            for c in part.chars() {
+                if !(c == '/' && key.ends_with('/')) {
+                    key.push(c);
+                }
+            }
+        }
+        key
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GResource {
+    pub files: Vec<ResourceFile>,
+}
+
+impl GResource {
+    // Parse the .gresource.xml at path, errors are printed
+    pub fn from(path: &Path) -> Option<GResource> {
+        let xml = std::fs::read_to_string(path).ok()?;
+        match GResource::parse(&xml) {
+            Ok(gresource) => Some(gresource),
+            Err(err) => {
+                println!("\x1b[1;31merror\x1b[0m: {}: {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    pub fn parse(xml: &str) -> Result<GResource, String> {
+        let doc = roxmltree::Document::parse(xml).map_err(|err| err.to_string())?;
+        let root = doc.root_element();
+        if !root.has_tag_name("gresources") {
+            return Err("root element must be <gresources>".to_string());
+        }
+
+        let mut files = Vec::new();
+        for gresource in root
+            .children()
+            .filter(|node| node.has_tag_name("gresource"))
+        {
+            let prefix = gresource.attribute("prefix").unwrap_or("/");
+            for file in gresource
+                .children()
+                .filter(|node| node.has_tag_name("file"))
+            {
+                let path = file.text().map(str::trim).unwrap_or("");
+                if path.is_empty() {
+                    return Err(format!(
+                        "<file> without a path on line {}",
+                        doc.text_pos_at(file.range().start).row
+                    ));
+                }
+                files.push(ResourceFile {
+                    prefix: prefix.to_string(),
+                    path: path.to_string(),
+                    alias: file.attribute("alias").map(str::to_string),
+                    compressed: file.attribute("compressed") == Some("true"),
+                    preprocess: file
+                        .attribute("preprocess")
+                        .map(|value| value.split(',').map(|p| p.trim().to_string()).collect())
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        Ok(GResource { files })
+    }
+
+    // Files the bundle is built from, same as
+    // glib-compile-resources --generate-dependencies
+    pub fn dependencies(&self, sourcedir: &Path) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|file| sourcedir.join(&file.path))
+            .collect()
+    }
+
+    // Print referenced files which doesn't exist and files
+    // in sourcedir nothing refers to. Fails if any is missing.
+    pub fn check(&self, sourcedir: &Path) -> Option<()> {
+        let dependencies = self.dependencies(sourcedir);
+
+        let mut missing = false;
+        for path in &dependencies {
+            if !path.is_file() {
+                println!(
+                    "\x1b[1;31merror\x1b[0m: {} is referenced in gresource but doesn't exist",
+                    path.display()
+                );
+                missing = true;
+            }
+        }
+
+        // SCSS partials are part of the stylesheets importing them
+        for path in walk(sourcedir) {
+            if !dependencies.contains(&path) && !is_partial(&path) {
+                println!(
+                    "\x1b[1;33mwarning\x1b[0m: {} isn't referenced in gresource",
+                    path.display()
+                );
+            }
+        }
+
+        if missing {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    // Write the bundle back, e.g. for glib-compile-resources
+    // after files were changed. Each file gets its own <gresource>.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gresources>\n");
+        for file in &self.files {
+            let mut attributes = String::new();
+            if let Some(alias) = &file.alias {
+                attributes.push_str(&format!(" alias=\"{}\"", escape(alias)));
+            }
+            if file.compressed {
+                attributes.push_str(" compressed=\"true\"");
+            }
+            if !file.preprocess.is_empty() {
+                attributes.push_str(&format!(
+                    " preprocess=\"{}\"",
+                    escape(&file.preprocess.join(","))
+                ));
+            }
+            xml.push_str(&format!(
+                "  <gresource prefix=\"{}\">\n    <file{}>{}</file>\n  </gresource>\n",
+                escape(&file.prefix),
+                attributes,
+                escape(&file.path)
+            ));
+        }
+        xml.push_str("</gresources>\n");
+        xml
+    }
+
+    // Whether compile() can build the bundle without glib-compile-resources
+    pub fn is_native(&self) -> bool {
+        self.files.iter().all(|file| {
+            file.preprocess
+                .iter()
+                .all(|preprocess| PREPROCESSORS.contains(&preprocess.as_str()))
+        })
+    }
+
+    // Build the .gresource bundle, same as glib-compile-resources.
+    // Each resource is stored as (uuay) of size, flags and data,
+    // data is zlib compressed or otherwise nul terminated.
+    pub fn compile(&self, sourcedir: &Path) -> Option<Vec<u8>> {
+        let mut table = HashTable::default();
+
+        for file in &self.files {
+            let mut data = std::fs::read(sourcedir.join(&file.path)).ok()?;
+            for preprocess in &file.preprocess {
+                let text = String::from_utf8(data).ok()?;
+                data = match preprocess.as_str() {
+                    "xml-stripblanks" => xml_stripblanks(&text),
+                    "json-stripblanks" => json_stripblanks(&text),
+                    _ => return None,
+                }
+                .into_bytes();
+            }
+
+            let size = data.len() as u32;
+            let (flags, content) = if file.compressed {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(&data).ok()?;
+                (FLAG_COMPRESSED, encoder.finish().ok()?)
+            } else {
+                data.push(0);
+                (0, data)
+            };
+
+            // Serialized GVariant "v" holding "(uuay)"
+            let mut value = Vec::new();
+            value.extend_from_slice(&size.to_le_bytes());
+            value.extend_from_slice(&flags.to_le_bytes());
+            value.extend_from_slice(&content);
+            value.push(0);
+            value.extend_from_slice(b"(uuay)");
+
+            let key = file.resource_path();
+            let item = table.insert(&key, Some(value));
+            insert_parents(&mut table, item, &key);
+        }
+
+        Some(table.serialize())
+    }
+}
+
+// Add directory entries for every level of key,
+// /io/foo/Bar/window.ui has /io/foo/Bar/, /io/foo/, /io/ and /
+fn insert_parents(table: &mut HashTable, item: usize, key: &str) {
+    let key = key.trim_end_matches('/');
+    if let Some(end) = key.rfind('/') {
+        let parentkey = &key[..=end];
+        let parent = match table.get(parentkey) {
+            Some(parent) => parent,
+            None => {
+                let parent = table.insert(parentkey, None);
+                insert_parents(table, parent, parentkey);
+                parent
+            }
+        };
+        table.set_parent(item, parent);
+    }
+}
+
+// Drop whitespace-only text between tags, like xmllint --noblanks.
+// Whitespace being the only content of an element is kept.
+fn xml_stripblanks(xml: &str) -> String {
+    let mut out = String::with_capacity(xml.len());
+    let mut rest = xml;
+    // Whether the last tag started an element, not a comment or the like
+    let mut opened = false;
+
+    while !rest.is_empty() {
+        if rest.starts_with('<') {
+            let end = if rest.starts_with("<!--") {
+                rest.find("-->").map(|end| end + 3)
+            } else if rest.starts_with("<![CDATA[") {
+                rest.find("]]>").map(|end| end + 3)
+            } else {
+                let mut quote = None;
+                rest.char_indices().find_map(|(i, c)| match (quote, c) {
+                    (None, '"') | (None, '\'') => {
+                        quote = Some(c);
+                        None
+                    }
+                    (Some(q), c) if q == c => {
+                        quote = None;
+                        None
+                    }
+                    (None, '>') => Some(i + 1),
+                    _ => None,
+                })
+            }
+            .unwrap_or(rest.len());
+
+            let tag = &rest[..end];
+            opened = !tag.starts_with("</")
+                && !tag.starts_with("<!")
+                && !tag.starts_with("<?")
+                && !tag.ends_with("/>");
+            out.push_str(tag);
+            rest = &rest[end..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            if !text.trim().is_empty() || (opened && rest[end..].starts_with("</")) {
+                out.push_str(text);
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    out
+}
+
+// Drop whitespace outside of strings, like json-glib-format --minimize
+fn json_stripblanks(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut instring = false;
+    let mut escaped = false;
+
+    for c in json.chars() {
+        if instring {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                instring = false;
+            }
+            out.push(c);
+        } else if c == '"' {
+            instring = true;
+            out.push(c);
+        } else if !c.is_whitespace() {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// All files under dir, recursively
+pub fn walk(dir: &Path) -> Vec<PathBuf> {
+    read_dir_sorted(dir)
+        .into_iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                walk(&path)
+            } else {
+                vec![path]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gvdb::reader::Table;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn strips_whitespace_between_tags() {
+        assert_eq!(
+            xml_stripblanks("<a>\n  <b/>\n  <c x=\"1\"></c>\n</a>\n"),
+            "<a><b/><c x=\"1\"></c></a>"
+        );
+        assert_eq!(xml_stripblanks("<a/>  <b/>"), "<a/><b/>");
+        assert_eq!(
+            xml_stripblanks("<?xml version=\"1.0\"?>\n<a>\n</a>"),
+            "<?xml version=\"1.0\"?><a>\n</a>"
+        );
+        // > inside attributes doesn't end the tag
+        assert_eq!(
+            xml_stripblanks("<a title=\"x > y\" alt='>'>\n  <b/>\n</a>"),
+            "<a title=\"x > y\" alt='>'><b/></a>"
+        );
+    }
+
+    #[test]
+    fn keeps_whitespace_only_content() {
+        assert_eq!(xml_stripblanks("<a>  </a>"), "<a>  </a>");
+        assert_eq!(xml_stripblanks("<a>\n  <b> </b>\n</a>"), "<a><b> </b></a>");
+        // After a closing or empty tag it's between elements
+        assert_eq!(xml_stripblanks("<a><b/> </a>"), "<a><b/></a>");
+        assert_eq!(xml_stripblanks("<a><b></b> </a>"), "<a><b></b></a>");
+    }
+
+    #[test]
+    fn keeps_mixed_content() {
+        assert_eq!(
+            xml_stripblanks("<p>\n  Hello <b>world</b>, bye\n</p>"),
+            "<p>\n  Hello <b>world</b>, bye\n</p>"
+        );
+        assert_eq!(
+            xml_stripblanks("<a>\n  <!-- <b> </b> -->\n  <![CDATA[ <c> ]]>\n</a>"),
+            "<a><!-- <b> </b> --><![CDATA[ <c> ]]></a>"
+        );
+    }
+
+    #[test]
+    fn handles_non_ascii_tags() {
+        assert_eq!(xml_stripblanks("<é>  </é>"), "<é>  </é>");
+        assert_eq!(
+            xml_stripblanks("<données>\n  <clé valeur=\"ü\"/>\n  <ß>  </ß>\n</données>"),
+            "<données><clé valeur=\"ü\"/><ß>  </ß></données>"
+        );
+        assert_eq!(xml_stripblanks("<a>日本 </a>"), "<a>日本 </a>");
+    }
+
+    #[test]
+    fn strips_json_whitespace() {
+        assert_eq!(
+            json_stripblanks("{\n  \"a b\": [1, 2],\n  \"c\": \"x \\\" y\"\n}\n"),
+            "{\"a b\":[1,2],\"c\":\"x \\\" y\"}"
+        );
+    }
+
+    #[test]
+    fn compiled_bundle_reads_back() {
+        let dir = std::env::temp_dir().join(format!("cargo-pkg-gresource-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("ui")).unwrap();
+        std::fs::write(dir.join("ui/window.ui"), "<a>\n  <b/>\n</a>\n").unwrap();
+        std::fs::write(dir.join("style.css"), ".a { color: red }").unwrap();
+        let xml = dir.join("bundle.gresource.xml");
+        std::fs::write(
+            &xml,
+            r#"<gresources>
+                <gresource prefix="/io/foo/Bar">
+                    <file preprocess="xml-stripblanks" alias="window.ui">ui/window.ui</file>
+                    <file compressed="true">style.css</file>
+                </gresource>
+            </gresources>"#,
+        )
+        .unwrap();
+
+        let gresource = GResource::from(&xml).unwrap();
+        assert!(gresource.is_native());
+        let data = gresource.compile(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let root = Table::root(&data);
+        assert_eq!(root.list("/").unwrap(), ["/io/"]);
+        assert_eq!(root.list("/io/foo/").unwrap(), ["/io/foo/Bar/"]);
+        assert_eq!(
+            root.list("/io/foo/Bar/").unwrap(),
+            ["/io/foo/Bar/style.css", "/io/foo/Bar/window.ui"]
+        );
+
+        // (uuay) of size, flags and the nul terminated data
+        let window = root.value("/io/foo/Bar/window.ui").unwrap();
+        assert_eq!(window, b"\x0b\0\0\0\0\0\0\0<a><b/></a>\0\0(uuay)");
+
+        let style = root.value("/io/foo/Bar/style.css").unwrap();
+        assert_eq!(&style[..8], b"\x11\0\0\0\x01\0\0\0");
+        assert!(style.ends_with(b"\0(uuay)"));
+        let mut css = String::new();
+        ZlibDecoder::new(&style[8..style.len() - 7])
+            .read_to_string(&mut css)
+            .unwrap();
+        assert_eq!(css, ".a { color: red }");
+    }
+
+    #[test]
+    fn parses_bundles() {
+        let gresource = GResource::parse(
+            r#"<gresources>
+                <gresource prefix="/io/foo/Bar">
+                    <file alias="w.ui"> ui/window.ui </file>
+                </gresource>
+            </gresources>"#,
+        )
+        .unwrap();
+        assert_eq!(gresource.files[0].path, "ui/window.ui");
+        assert_eq!(gresource.files[0].resource_path(), "/io/foo/Bar/w.ui");
+
+        let err = GResource::parse(
+            "<gresources>\n  <gresource>\n    <file/>\n  </gresource>\n</gresources>",
+        )
+        .unwrap_err();
+        assert_eq!(err, "<file> without a path on line 3");
+        let err = GResource::parse("<gresource><file>a</file></gresource>").unwrap_err();
+        assert_eq!(err, "root element must be <gresources>");
+        assert!(GResource::parse("<gresources>").is_err());
+    }
+
+    #[test]
+    fn resource_paths_join_prefix() {
+        let file = |prefix: &str, alias: Option<&str>| ResourceFile {
+            prefix: prefix.to_string(),
+            path: "ui/window.ui".to_string(),
+            alias: alias.map(str::to_string),
+            compressed: false,
+            preprocess: Vec::new(),
+        };
+        assert_eq!(
+            file("/io/foo/", None).resource_path(),
+            "/io/foo/ui/window.ui"
+        );
+        assert_eq!(file("io/foo", None).resource_path(), "/io/foo/ui/window.ui");
+        assert_eq!(file("/", Some("w.ui")).resource_path(), "/w.ui");
+    }
+}
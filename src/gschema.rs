@@ -0,0 +1,984 @@
+use super::fingerprint::read_dir_sorted;
+use super::gvariant::{self, Value};
+use super::gvdb::HashTable;
+use std::path::{Path, PathBuf};
+
+// <enum> or <flags> definition
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub id: String,
+    pub file: PathBuf,
+    pub is_flags: bool,
+    // Nick and numeric value
+    pub values: Vec<(String, i64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub name: String,
+    // GVariant type, "s" for enums and "as" for flags
+    pub ty: String,
+    pub enum_id: Option<String>,
+    pub is_flags: bool,
+    pub default: String,
+    pub summary: Option<String>,
+    // 'm' for messages or 't' for time, and context
+    pub l10n: Option<(char, Option<String>)>,
+    pub choices: Vec<String>,
+    // Alias and target
+    pub aliases: Vec<(String, String)>,
+    pub range: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub id: String,
+    pub file: PathBuf,
+    pub path: Option<String>,
+    pub gettext_domain: Option<String>,
+    pub extends: Option<String>,
+    pub list_of: Option<String>,
+    pub keys: Vec<Key>,
+    // Child name and schema id
+    pub children: Vec<(String, String)>,
+    // Key name and default of <override>
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SchemaList {
+    pub schemas: Vec<Schema>,
+    pub enums: Vec<Enum>,
+}
+
+impl SchemaList {
+    // Parse every file, errors are printed
+    pub fn from(files: &[PathBuf]) -> Option<SchemaList> {
+        let mut list = SchemaList::default();
+        let mut failed = false;
+        for file in files {
+            if let Err(err) = list.read(file) {
+                println!("\x1b[1;31merror\x1b[0m: {}: {}", file.display(), err);
+                failed = true;
+            }
+        }
+
+        if failed {
+            None
+        } else {
+            Some(list)
+        }
+    }
+
+    // Add the schemas of file, nothing if it has errors
+    fn read(&mut self, file: &Path) -> Result<(), String> {
+        let xml = std::fs::read_to_string(file).map_err(|err| err.to_string())?;
+        let list = SchemaList::parse(&xml, file)?;
+        self.schemas.extend(list.schemas);
+        self.enums.extend(list.enums);
+        Ok(())
+    }
+
+    // Schemas and enums of the contents of file
+    pub fn parse(xml: &str, file: &Path) -> Result<SchemaList, String> {
+        let mut list = SchemaList::default();
+        let doc = roxmltree::Document::parse(xml).map_err(|err| err.to_string())?;
+        let root = doc.root_element();
+        if !root.has_tag_name("schemalist") {
+            return Err("root element must be <schemalist>".to_string());
+        }
+        let domain = root.attribute("gettext-domain");
+
+        for node in root.children().filter(|node| node.is_element()) {
+            match node.tag_name().name() {
+                "schema" => {
+                    let schema = parse_schema(node, domain, file)?;
+                    list.schemas.push(schema);
+                }
+                "enum" | "flags" => {
+                    let is_flags = node.has_tag_name("flags");
+                    let mut values = Vec::new();
+                    for value in node.children().filter(|node| node.has_tag_name("value")) {
+                        let nick = required(value, "nick")?;
+                        let number = required(value, "value")?;
+                        let number = match gvariant::parse("x", number) {
+                            Ok(Value::Int64(number)) => number,
+                            _ => return Err(format!("invalid value \"{}\" for {}", number, nick)),
+                        };
+                        values.push((nick.to_string(), number));
+                    }
+                    list.enums.push(Enum {
+                        id: required(node, "id")?.to_string(),
+                        file: file.to_path_buf(),
+                        is_flags,
+                        values,
+                    });
+                }
+                name => return Err(format!("unexpected <{}> in <schemalist>", name)),
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn schema(&self, id: &str) -> Option<&Schema> {
+        self.schemas.iter().find(|schema| schema.id == id)
+    }
+
+    pub fn enumeration(&self, id: &str) -> Option<&Enum> {
+        self.enums.iter().find(|e| e.id == id)
+    }
+
+    // Same checks glib-compile-schemas does,
+    // returns the file and message of every problem.
+    pub fn validate(&self) -> Vec<(PathBuf, String)> {
+        let mut errors = Vec::new();
+
+        for (i, e) in self.enums.iter().enumerate() {
+            let mut error = |message: String| {
+                errors.push((e.file.clone(), format!("enum \"{}\": {}", e.id, message)))
+            };
+
+            if self.enums[..i].iter().any(|other| other.id == e.id) {
+                error("enum is defined twice".to_string());
+            }
+            if e.values.is_empty() {
+                error("enum has no values".to_string());
+            }
+            for (j, (nick, value)) in e.values.iter().enumerate() {
+                if nick.len() < 2 {
+                    error(format!("nick \"{}\" must be at least 2 characters", nick));
+                }
+                if e.values[..j].iter().any(|(other, _)| other == nick) {
+                    error(format!("nick \"{}\" is repeated", nick));
+                }
+                if e.is_flags && (*value <= 0 || value & (value - 1) != 0) {
+                    error(format!(
+                        "value of \"{}\" must have exactly one bit set",
+                        nick
+                    ));
+                }
+            }
+        }
+
+        for (i, schema) in self.schemas.iter().enumerate() {
+            let mut error = |message: String| {
+                errors.push((
+                    schema.file.clone(),
+                    format!("schema \"{}\": {}", schema.id, message),
+                ))
+            };
+
+            if !is_valid_schema_id(&schema.id) {
+                error("invalid schema id".to_string());
+            }
+            if self.schemas[..i].iter().any(|other| other.id == schema.id) {
+                error("schema is defined twice".to_string());
+            }
+            if let Some(path) = &schema.path {
+                if !path.starts_with('/') || !path.ends_with('/') || path.contains("//") {
+                    error(format!(
+                        "path \"{}\" must begin and end with '/' and not contain '//'",
+                        path
+                    ));
+                }
+            }
+            for (attribute, reference) in
+                &[("extends", &schema.extends), ("list-of", &schema.list_of)]
+            {
+                if let Some(id) = reference {
+                    if self.schema(id).is_none() {
+                        error(format!("{} schema \"{}\" isn't defined", attribute, id));
+                    }
+                }
+            }
+            // Following a cycle would never end
+            let mut parent = schema.extends.as_deref();
+            let mut depth = 0;
+            while let Some(id) = parent.filter(|_| depth <= self.schemas.len()) {
+                parent = self.schema(id).and_then(|parent| parent.extends.as_deref());
+                depth += 1;
+            }
+            if depth > self.schemas.len() {
+                error("schema extends itself".to_string());
+                continue;
+            }
+            for (name, id) in &schema.children {
+                if self.schema(id).is_none() {
+                    error(format!(
+                        "child \"{}\" refers to undefined schema \"{}\"",
+                        name, id
+                    ));
+                }
+            }
+            for (name, default) in &schema.overrides {
+                match self.inherited_key(schema, name) {
+                    Some(key) => {
+                        let key = Key {
+                            default: default.clone(),
+                            ..key.clone()
+                        };
+                        if let Err(err) = self.default_value(&key) {
+                            error(format!("override \"{}\": {}", name, err));
+                        }
+                    }
+                    None => error(format!("override \"{}\" has no key to override", name)),
+                }
+            }
+
+            for (j, key) in schema.keys.iter().enumerate() {
+                let mut error = |message: String| {
+                    error(format!("key \"{}\": {}", key.name, message));
+                };
+
+                if !is_valid_key_name(&key.name) {
+                    error(
+                        "key names may only contain lowercase letters, digits and '-', \
+                         start with a letter and not contain '--' or end with '-'"
+                            .to_string(),
+                    );
+                }
+                if schema.keys[..j].iter().any(|other| other.name == key.name) {
+                    error("key is defined twice".to_string());
+                }
+
+                if let Some(id) = &key.enum_id {
+                    match self.enumeration(id) {
+                        Some(e) if e.is_flags != key.is_flags => error(format!(
+                            "\"{}\" is {}",
+                            id,
+                            if e.is_flags {
+                                "flags, not an enum"
+                            } else {
+                                "an enum, not flags"
+                            }
+                        )),
+                        Some(_) => {}
+                        None => error(format!(
+                            "{} \"{}\" isn't defined",
+                            if key.is_flags { "flags" } else { "enum" },
+                            id
+                        )),
+                    }
+                } else if !gvariant::is_valid_type(&key.ty) {
+                    error(format!("invalid type \"{}\"", key.ty));
+                    continue;
+                }
+
+                if !key.choices.is_empty() && !["s", "as", "ms"].contains(&key.ty.as_str()) {
+                    error("<choices> are only allowed for string keys".to_string());
+                }
+
+                if let Err(err) = self.default_value(key) {
+                    error(err);
+                }
+
+                if let Some((min, max)) = &key.range {
+                    if !"ynqiuxtd".contains(key.ty.as_str()) || key.ty.len() != 1 {
+                        error("<range> is only allowed for numeric keys".to_string());
+                        continue;
+                    }
+                    match (gvariant::parse(&key.ty, min), gvariant::parse(&key.ty, max)) {
+                        (Ok(min), Ok(max)) => {
+                            if min.as_f64() > max.as_f64() {
+                                error("<range> min is greater than max".to_string());
+                            }
+                        }
+                        (Err(err), _) | (_, Err(err)) => error(format!("<range>: {}", err)),
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
+    // Print validation errors, fails if there are any
+    pub fn check(&self) -> Option<()> {
+        let errors = self.validate();
+        for (file, error) in &errors {
+            println!("\x1b[1;31merror\x1b[0m: {}: {}", file.display(), error);
+        }
+        if errors.is_empty() {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // Load every schema in dir like glib-compile-schemas does,
+    // files with errors are skipped and .gschema.override applied.
+    pub fn from_dir(dir: &Path) -> SchemaList {
+        let files = read_dir_sorted(dir);
+        let mut list = SchemaList::default();
+        for file in files.iter().filter(|file| is_schema_file(file)) {
+            if let Err(err) = list.read(file) {
+                warn_ignored(file, &err);
+            }
+        }
+
+        // Dropping a file can break others referring to it
+        loop {
+            let errors = list.validate();
+            if errors.is_empty() {
+                break;
+            }
+            for (file, error) in &errors {
+                warn_ignored(file, error);
+            }
+            list.schemas
+                .retain(|schema| !errors.iter().any(|(file, _)| *file == schema.file));
+            list.enums
+                .retain(|e| !errors.iter().any(|(file, _)| *file == e.file));
+        }
+
+        for file in files
+            .iter()
+            .filter(|file| file.to_string_lossy().ends_with(".gschema.override"))
+        {
+            if let Err(err) = list.apply_overrides(file) {
+                warn_ignored(file, &err);
+            }
+        }
+        list
+    }
+
+    // Key file of [schema.id] groups with key=value defaults
+    fn apply_overrides(&mut self, file: &Path) -> Result<(), String> {
+        let data = std::fs::read_to_string(file).map_err(|err| err.to_string())?;
+        let mut overrides = Vec::new();
+        let mut group = None;
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            } else if line.starts_with('[') && line.ends_with(']') {
+                group = Some(line[1..line.len() - 1].to_string());
+            } else if let (Some(group), Some(eq)) = (&group, line.find('=')) {
+                // Desktop specific overrides aren't supported
+                if !group.contains(':') {
+                    let key = line[..eq].trim().to_string();
+                    overrides.push((group.clone(), key, line[eq + 1..].trim().to_string()));
+                }
+            } else {
+                return Err(format!("invalid line \"{}\"", line));
+            }
+        }
+
+        for (id, name, default) in overrides {
+            let key = self
+                .schema(&id)
+                .and_then(|schema| schema.keys.iter().find(|key| key.name == name))
+                .ok_or_else(|| format!("no key \"{}\" in schema \"{}\"", name, id))?;
+            let key = Key {
+                default,
+                l10n: None,
+                ..key.clone()
+            };
+            self.default_value(&key)
+                .map_err(|err| format!("{} {}: {}", id, name, err))?;
+
+            for schema in self.schemas.iter_mut().filter(|schema| schema.id == id) {
+                for k in schema.keys.iter_mut().filter(|k| k.name == name) {
+                    *k = key.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Keys of a schema followed by the ones it inherits
+    pub fn keys<'a>(&'a self, schema: &'a Schema) -> Vec<&'a Key> {
+        let mut keys = schema.keys.iter().collect::<Vec<_>>();
+        if let Some(parent) = schema.extends.as_ref().and_then(|id| self.schema(id)) {
+            for key in self.keys(parent) {
+                if !keys.iter().any(|k| k.name == key.name) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    // Keys of a schema including those from the schema it extends
+    fn inherited_key<'a>(&'a self, schema: &'a Schema, name: &str) -> Option<&'a Key> {
+        schema
+            .keys
+            .iter()
+            .find(|key| key.name == name)
+            .or_else(|| self.inherited_key(self.schema(schema.extends.as_ref()?)?, name))
+    }
+
+    // Parse and check the default value of a key
+    fn default_value(&self, key: &Key) -> Result<Value, String> {
+        let value = gvariant::parse(&key.ty, key.default.trim())
+            .map_err(|err| format!("invalid default: {}", err))?;
+
+        // Strings the key may hold, for enums, flags and choices
+        let allowed = match &key.enum_id {
+            Some(id) => self
+                .enumeration(id)
+                .map(|e| e.values.iter().map(|(nick, _)| nick.clone()).collect())
+                .unwrap_or_default(),
+            None => key.choices.clone(),
+        };
+        if !allowed.is_empty() {
+            let strings = match &value {
+                Value::Array(_, values) => values.iter().filter_map(Value::as_str).collect(),
+                Value::Maybe(_, Some(value)) => value.as_str().into_iter().collect(),
+                value => value.as_str().into_iter().collect::<Vec<_>>(),
+            };
+            for string in strings {
+                let aliased = key.aliases.iter().any(|(alias, _)| alias == string);
+                if !allowed.iter().any(|nick| nick == string) && !aliased {
+                    return Err(format!("default \"{}\" isn't a valid choice", string));
+                }
+            }
+        }
+        for (alias, target) in &key.aliases {
+            if !allowed.contains(target) {
+                return Err(format!(
+                    "alias \"{}\" targets unknown \"{}\"",
+                    alias, target
+                ));
+            }
+        }
+
+        if let Some((min, max)) = &key.range {
+            let number = value.as_f64();
+            let min = gvariant::parse(&key.ty, min).ok().and_then(|v| v.as_f64());
+            let max = gvariant::parse(&key.ty, max).ok().and_then(|v| v.as_f64());
+            if number < min || number > max {
+                return Err("default is out of <range>".to_string());
+            }
+        }
+
+        Ok(value)
+    }
+
+    // Build gschemas.compiled, a GVDB table of schema ids
+    // each pointing to a table of its keys.
+    pub fn compile(&self) -> Option<Vec<u8>> {
+        let mut root = HashTable::default();
+        // Lists every schema, like glib-compile-schemas does
+        let ids = root.insert("", None);
+
+        for schema in &self.schemas {
+            let mut table = HashTable::default();
+            let keys = table.insert("", None);
+            let mut l10n = false;
+
+            let overrides = schema.overrides.iter().filter_map(|(name, default)| {
+                let key = self.inherited_key(self.schema(schema.extends.as_ref()?)?, name)?;
+                Some(Key {
+                    default: default.clone(),
+                    ..key.clone()
+                })
+            });
+            for key in schema.keys.iter().cloned().chain(overrides) {
+                l10n |= key.l10n.is_some();
+                let value = self.serialize_key(&key).ok()?;
+                let item = table.insert(&key.name, Some(variant(value)));
+                table.set_parent(item, keys);
+            }
+
+            for (name, id) in &schema.children {
+                let item = table.insert(
+                    &(name.clone() + "/"),
+                    Some(variant(Value::String(id.clone()))),
+                );
+                table.set_parent(item, keys);
+            }
+
+            let strings = [
+                (".path", &schema.path),
+                (".extends", &schema.extends),
+                (".list-of", &schema.list_of),
+            ];
+            for (name, value) in strings.iter() {
+                if let Some(value) = value {
+                    table.insert(name, Some(variant(Value::String(value.clone()))));
+                }
+            }
+            if let (true, Some(domain)) = (l10n, &schema.gettext_domain) {
+                table.insert(
+                    ".gettext-domain",
+                    Some(variant(Value::String(domain.clone()))),
+                );
+            }
+
+            let item = root.insert_table(&schema.id, table);
+            root.set_parent(item, ids);
+        }
+
+        Some(root.serialize())
+    }
+
+    // A key is stored as a tuple of its default followed by
+    // optional (y...) entries for translation, choices and range
+    fn serialize_key(&self, key: &Key) -> Result<Value, String> {
+        let mut members = vec![self.default_value(key)?];
+
+        if let Some((category, context)) = &key.l10n {
+            let mut message = key.default.trim().to_string();
+            if let Some(context) = context {
+                message = format!("{}\u{4}{}", context, message);
+            }
+            members.push(Value::Tuple(vec![
+                Value::Byte(b'l'),
+                Value::Tuple(vec![Value::Byte(*category as u8), Value::String(message)]),
+            ]));
+        }
+
+        // String to value map, see glib's gstrinfo.c
+        let mut strinfo: Vec<u32> = Vec::new();
+        let (kind, values) = match &key.enum_id {
+            Some(id) => {
+                let e = self.enumeration(id).ok_or("undefined enum")?;
+                (if e.is_flags { b'f' } else { b'e' }, e.values.clone())
+            }
+            None => (b'c', key.choices.iter().map(|c| (c.clone(), 0)).collect()),
+        };
+        for (nick, value) in &values {
+            strinfo.push(*value as u32);
+            strinfo.extend(strinfo_words(nick, false));
+        }
+        for (alias, target) in &key.aliases {
+            let words = strinfo_words(target, false);
+            let index = (1..strinfo.len())
+                .find(|&i| strinfo[i..].starts_with(&words))
+                .ok_or("alias target not found")?;
+            strinfo.push(index as u32 - 1);
+            strinfo.extend(strinfo_words(alias, true));
+        }
+        if !strinfo.is_empty() {
+            members.push(Value::Tuple(vec![
+                Value::Byte(kind),
+                Value::Array(
+                    "u".to_string(),
+                    strinfo.into_iter().map(Value::Uint32).collect(),
+                ),
+            ]));
+        }
+
+        if let Some((min, max)) = &key.range {
+            members.push(Value::Tuple(vec![
+                Value::Byte(b'r'),
+                Value::Tuple(vec![
+                    gvariant::parse(&key.ty, min)?,
+                    gvariant::parse(&key.ty, max)?,
+                ]),
+            ]));
+        }
+
+        Ok(Value::Tuple(members))
+    }
+}
+
+pub fn is_schema_file(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".gschema.xml") || name.ends_with(".enums.xml")
+}
+
+fn warn_ignored(file: &Path, error: &str) {
+    println!(
+        "\x1b[1;33mwarning\x1b[0m: {}: {}, file is ignored",
+        file.display(),
+        error
+    );
+}
+
+fn variant(value: Value) -> Vec<u8> {
+    Value::Variant(Box::new(value)).serialize()
+}
+
+// String packed into words as 0xff (0xfe for aliases),
+// the string, nul padding and a final 0xff byte.
+fn strinfo_words(string: &str, alias: bool) -> Vec<u32> {
+    let n_words = std::cmp::max(2, (string.len() + 6) >> 2);
+    let mut bytes = vec![0u8; n_words * 4];
+    bytes[0] = if alias { 0xfe } else { 0xff };
+    bytes[1..=string.len()].copy_from_slice(string.as_bytes());
+    bytes[n_words * 4 - 1] = 0xff;
+    bytes
+        .chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
+fn parse_schema(
+    node: roxmltree::Node,
+    domain: Option<&str>,
+    file: &Path,
+) -> Result<Schema, String> {
+    let mut schema = Schema {
+        id: required(node, "id")?.to_string(),
+        file: file.to_path_buf(),
+        path: node.attribute("path").map(str::to_string),
+        gettext_domain: node
+            .attribute("gettext-domain")
+            .or(domain)
+            .map(str::to_string),
+        extends: node.attribute("extends").map(str::to_string),
+        list_of: node.attribute("list-of").map(str::to_string),
+        keys: Vec::new(),
+        children: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    for child in node.children().filter(|node| node.is_element()) {
+        match child.tag_name().name() {
+            "key" => schema.keys.push(parse_key(child)?),
+            "child" => schema.children.push((
+                required(child, "name")?.to_string(),
+                required(child, "schema")?.to_string(),
+            )),
+            "override" => schema.overrides.push((
+                required(child, "name")?.to_string(),
+                child.text().unwrap_or_default().to_string(),
+            )),
+            name => return Err(format!("unexpected <{}> in schema \"{}\"", name, schema.id)),
+        }
+    }
+
+    Ok(schema)
+}
+
+fn parse_key(node: roxmltree::Node) -> Result<Key, String> {
+    let name = required(node, "name")?.to_string();
+    let (ty, enum_id, is_flags) = match (
+        node.attribute("type"),
+        node.attribute("enum"),
+        node.attribute("flags"),
+    ) {
+        (Some(ty), None, None) => (ty.to_string(), None, false),
+        (None, Some(id), None) => ("s".to_string(), Some(id.to_string()), false),
+        (None, None, Some(id)) => ("as".to_string(), Some(id.to_string()), true),
+        _ => {
+            return Err(format!(
+                "key \"{}\" needs exactly one of type, enum or flags",
+                name
+            ))
+        }
+    };
+
+    let mut key = Key {
+        name,
+        ty,
+        enum_id,
+        is_flags,
+        default: String::new(),
+        summary: None,
+        l10n: None,
+        choices: Vec::new(),
+        aliases: Vec::new(),
+        range: None,
+    };
+
+    let mut has_default = false;
+    for child in node.children().filter(|node| node.is_element()) {
+        match child.tag_name().name() {
+            "default" => {
+                has_default = true;
+                key.default = child.text().unwrap_or_default().to_string();
+                key.l10n = match child.attribute("l10n") {
+                    Some("messages") => Some(('m', child.attribute("context").map(str::to_string))),
+                    Some("time") => Some(('t', child.attribute("context").map(str::to_string))),
+                    Some(other) => {
+                        return Err(format!("key \"{}\": unknown l10n \"{}\"", key.name, other))
+                    }
+                    None => None,
+                };
+            }
+            "summary" => key.summary = child.text().map(|text| text.trim().to_string()),
+            "description" => {}
+            "choices" => {
+                for choice in child.children().filter(|node| node.has_tag_name("choice")) {
+                    key.choices.push(required(choice, "value")?.to_string());
+                }
+            }
+            "aliases" => {
+                for alias in child.children().filter(|node| node.has_tag_name("alias")) {
+                    key.aliases.push((
+                        required(alias, "value")?.to_string(),
+                        required(alias, "target")?.to_string(),
+                    ));
+                }
+            }
+            "range" => {
+                key.range = Some((
+                    required(child, "min")?.to_string(),
+                    required(child, "max")?.to_string(),
+                ))
+            }
+            name => return Err(format!("unexpected <{}> in key \"{}\"", name, key.name)),
+        }
+    }
+
+    if !has_default {
+        return Err(format!("key \"{}\" has no <default>", key.name));
+    }
+    Ok(key)
+}
+
+fn required<'a>(node: roxmltree::Node<'a, '_>, attribute: &str) -> Result<&'a str, String> {
+    node.attribute(attribute).ok_or_else(|| {
+        format!(
+            "<{}> is missing the \"{}\" attribute",
+            node.tag_name().name(),
+            attribute
+        )
+    })
+}
+
+fn is_valid_schema_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+// Lowercase letters, digits and dashes, starting with
+// a letter, no double or trailing dash
+pub fn is_valid_key_name(name: &str) -> bool {
+    name.len() <= 1024
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.contains("--")
+        && !name.ends_with('-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gvdb::reader::Table;
+
+    fn schemas(xml: &str) -> SchemaList {
+        SchemaList::parse(xml, Path::new("test.gschema.xml")).expect("schema doesn't parse")
+    }
+
+    // Validation errors of a single key of type ty
+    fn key_errors(ty: &str, default: &str, extra: &str) -> Vec<String> {
+        let list = schemas(&format!(
+            r#"<schemalist>
+                <enum id="io.foo.Color">
+                    <value nick="red" value="0"/>
+                    <value nick="blue" value="1"/>
+                </enum>
+                <schema id="io.foo" path="/io/foo/">
+                    <key name="key" {}><default>{}</default>{}</key>
+                </schema>
+            </schemalist>"#,
+            ty, default, extra
+        ));
+        list.validate().into_iter().map(|(_, err)| err).collect()
+    }
+
+    #[test]
+    fn parses_enums_and_flags() {
+        let list = schemas(
+            r#"<schemalist>
+                <enum id="io.foo.Color">
+                    <value nick="red" value="0"/>
+                    <value nick="blue" value="0x10"/>
+                </enum>
+                <flags id="io.foo.Style">
+                    <value nick="bold" value="1"/>
+                    <value nick="italic" value="2"/>
+                </flags>
+            </schemalist>"#,
+        );
+        let color = list.enumeration("io.foo.Color").unwrap();
+        assert!(!color.is_flags);
+        assert_eq!(
+            color.values,
+            [("red".to_string(), 0), ("blue".to_string(), 16)]
+        );
+        assert!(list.enumeration("io.foo.Style").unwrap().is_flags);
+        assert!(list.validate().is_empty());
+    }
+
+    #[test]
+    fn parses_keys() {
+        let list = schemas(
+            r#"<schemalist gettext-domain="foo">
+                <schema id="io.foo" path="/io/foo/">
+                    <key name="color" enum="io.foo.Color"><default>'red'</default></key>
+                    <key name="style" flags="io.foo.Style"><default>[]</default></key>
+                    <key name="size" type="i">
+                        <default>5</default>
+                        <range min="0" max="10"/>
+                    </key>
+                    <key name="title" type="ms"><default>nothing</default></key>
+                    <key name="names" type="as">
+                        <default l10n="messages" context="names">['a']</default>
+                    </key>
+                    <key name="options" type="a{sv}"><default>{'a': &lt;1&gt;}</default></key>
+                </schema>
+            </schemalist>"#,
+        );
+        let keys = &list.schema("io.foo").unwrap().keys;
+        assert_eq!(
+            list.schema("io.foo").unwrap().gettext_domain.as_deref(),
+            Some("foo")
+        );
+        assert_eq!(keys[0].ty, "s");
+        assert_eq!(keys[0].enum_id.as_deref(), Some("io.foo.Color"));
+        assert_eq!(keys[1].ty, "as");
+        assert!(keys[1].is_flags);
+        assert_eq!(keys[2].range, Some(("0".to_string(), "10".to_string())));
+        assert_eq!(keys[3].ty, "ms");
+        assert_eq!(keys[4].l10n, Some(('m', Some("names".to_string()))));
+        assert_eq!(keys[5].default, "{'a': <1>}");
+    }
+
+    #[test]
+    fn accepts_valid_defaults() {
+        assert!(key_errors("type=\"i\"", "5", "<range min=\"0\" max=\"10\"/>").is_empty());
+        assert!(key_errors("enum=\"io.foo.Color\"", "'blue'", "").is_empty());
+        assert!(key_errors("type=\"ms\"", "just 'a'", "").is_empty());
+        assert!(key_errors("type=\"a{sv}\"", "{}", "").is_empty());
+        assert!(key_errors(
+            "type=\"s\"",
+            "'b'",
+            "<choices><choice value=\"a\"/><choice value=\"b\"/></choices>"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_defaults() {
+        let error = |ty: &str, default: &str, extra: &str| {
+            let errors = key_errors(ty, default, extra);
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            errors[0].clone()
+        };
+        assert!(error("type=\"i\"", "'a'", "").contains("invalid default"));
+        assert!(error("type=\"y\"", "300", "").contains("invalid default"));
+        assert!(error("type=\"as\"", "['a'", "").contains("invalid default"));
+        assert!(error("enum=\"io.foo.Color\"", "'green'", "").contains("isn't a valid choice"));
+        assert!(error(
+            "type=\"s\"",
+            "'c'",
+            "<choices><choice value=\"a\"/></choices>"
+        )
+        .contains("isn't a valid choice"));
+        assert!(error("flags=\"io.foo.Color\"", "[]", "").contains("is an enum, not flags"));
+        assert!(error("type=\"ii\"", "1", "").contains("invalid type"));
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let range = "<range min=\"0\" max=\"10\"/>";
+        assert_eq!(
+            key_errors("type=\"i\"", "11", range),
+            ["schema \"io.foo\": key \"key\": default is out of <range>"]
+        );
+        assert!(key_errors("type=\"i\"", "-1", range)[0].contains("out of <range>"));
+        assert!(
+            key_errors("type=\"i\"", "5", "<range min=\"10\" max=\"0\"/>")
+                .iter()
+                .any(|err| err.contains("min is greater than max"))
+        );
+        assert!(key_errors("type=\"s\"", "'a'", range)
+            .iter()
+            .any(|err| err.contains("only allowed for numeric keys")));
+        assert!(
+            key_errors("type=\"y\"", "1", "<range min=\"0\" max=\"256\"/>")
+                .iter()
+                .any(|err| err.contains("<range>"))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_enums_and_names() {
+        let list = schemas(
+            r#"<schemalist>
+                <flags id="io.foo.Style">
+                    <value nick="bold" value="3"/>
+                    <value nick="b" value="4"/>
+                </flags>
+                <schema id="io.foo">
+                    <key name="Bad_Name" type="s"><default>''</default></key>
+                    <key name="other" enum="io.foo.Missing"><default>'a'</default></key>
+                </schema>
+            </schemalist>"#,
+        );
+        let errors = list
+            .validate()
+            .into_iter()
+            .map(|(_, err)| err)
+            .collect::<Vec<_>>();
+        let has = |text: &str| errors.iter().any(|err| err.contains(text));
+        assert!(has("exactly one bit set"), "{:?}", errors);
+        assert!(has("at least 2 characters"), "{:?}", errors);
+        assert!(has("key names may only contain"), "{:?}", errors);
+        assert!(has("enum \"io.foo.Missing\" isn't defined"), "{:?}", errors);
+    }
+
+    #[test]
+    fn compiled_schemas_read_back() {
+        let list = schemas(
+            r#"<schemalist gettext-domain="foo">
+                <enum id="io.foo.Color">
+                    <value nick="red" value="0"/>
+                    <value nick="blue" value="1"/>
+                </enum>
+                <schema id="io.foo" path="/io/foo/">
+                    <key name="answer" type="i"><default>42</default></key>
+                    <key name="color" enum="io.foo.Color"><default>'blue'</default></key>
+                    <key name="size" type="i">
+                        <default>5</default>
+                        <range min="0" max="10"/>
+                    </key>
+                    <key name="title" type="s"><default l10n="messages">'Foo'</default></key>
+                    <child name="window" schema="io.foo.Window"/>
+                </schema>
+                <schema id="io.foo.Window">
+                    <key name="width" type="i"><default>600</default></key>
+                </schema>
+            </schemalist>"#,
+        );
+        let data = list.compile().unwrap();
+        let root = Table::root(&data);
+        assert_eq!(root.list("").unwrap(), ["io.foo", "io.foo.Window"]);
+
+        let schema = root.table("io.foo").unwrap();
+        let mut keys = schema.list("").unwrap();
+        keys.sort();
+        assert_eq!(keys, ["answer", "color", "size", "title", "window/"]);
+        // Variant of the (i) tuple holding the default
+        assert_eq!(schema.value("answer").unwrap(), b"\x2a\0\0\0\0(i)");
+        assert_eq!(schema.value(".path").unwrap(), b"/io/foo/\0\0s");
+        assert_eq!(schema.value(".gettext-domain").unwrap(), b"foo\0\0s");
+        assert_eq!(schema.value("window/").unwrap(), b"io.foo.Window\0\0s");
+
+        let range = Value::Tuple(vec![
+            Value::Int32(5),
+            Value::Tuple(vec![
+                Value::Byte(b'r'),
+                Value::Tuple(vec![Value::Int32(0), Value::Int32(10)]),
+            ]),
+        ]);
+        assert_eq!(schema.value("size").unwrap(), variant(range));
+
+        // Enum values with their nicks packed as strinfo
+        let color = schema.value("color").unwrap();
+        assert!(color.starts_with(b"blue\0"));
+        assert!(color.ends_with(b"(s(yau))"));
+        let strinfo = [0u32, 0x6465_72ff, 0xff00_0000, 1, 0x756c_62ff, 0xff00_0065];
+        let strinfo = strinfo
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert!(color.windows(strinfo.len()).any(|window| window == strinfo));
+
+        let window = root.table("io.foo.Window").unwrap();
+        assert_eq!(window.value("width").unwrap(), b"\x58\x02\0\0\0(i)");
+        assert!(window.value(".path").is_none());
+        assert!(root.table("io.foo.Missing").is_none());
+    }
+}
@@ -0,0 +1,799 @@
+// Parser for the GVariant text format and serializer for
+// the binary one, enough to handle GSettings schemas.
+// Types are kept as GVariant type strings, e.g. "a{sv}".
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Byte(u8),
+    Int16(i16),
+    Uint16(u16),
+    Int32(i32),
+    Uint32(u32),
+    Int64(i64),
+    Uint64(u64),
+    Handle(i32),
+    Double(f64),
+    String(String),
+    ObjectPath(String),
+    Signature(String),
+    Variant(Box<Value>),
+    // Element type and value
+    Maybe(String, Option<Box<Value>>),
+    // Element type and values
+    Array(String, Vec<Value>),
+    Tuple(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+}
+
+impl Value {
+    pub fn type_string(&self) -> String {
+        match self {
+            Value::Boolean(_) => "b".to_string(),
+            Value::Byte(_) => "y".to_string(),
+            Value::Int16(_) => "n".to_string(),
+            Value::Uint16(_) => "q".to_string(),
+            Value::Int32(_) => "i".to_string(),
+            Value::Uint32(_) => "u".to_string(),
+            Value::Int64(_) => "x".to_string(),
+            Value::Uint64(_) => "t".to_string(),
+            Value::Handle(_) => "h".to_string(),
+            Value::Double(_) => "d".to_string(),
+            Value::String(_) => "s".to_string(),
+            Value::ObjectPath(_) => "o".to_string(),
+            Value::Signature(_) => "g".to_string(),
+            Value::Variant(_) => "v".to_string(),
+            Value::Maybe(ty, _) => format!("m{}", ty),
+            Value::Array(ty, _) => format!("a{}", ty),
+            Value::Tuple(values) => format!(
+                "({})",
+                values.iter().map(Value::type_string).collect::<String>()
+            ),
+            Value::DictEntry(key, value) => {
+                format!("{{{}{}}}", key.type_string(), value.type_string())
+            }
+        }
+    }
+
+    // Numeric value for range checks
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(match self {
+            Value::Byte(v) => f64::from(*v),
+            Value::Int16(v) => f64::from(*v),
+            Value::Uint16(v) => f64::from(*v),
+            Value::Int32(v) | Value::Handle(v) => f64::from(*v),
+            Value::Uint32(v) => f64::from(*v),
+            Value::Int64(v) => *v as f64,
+            Value::Uint64(v) => *v as f64,
+            Value::Double(v) => *v,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::ObjectPath(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            Value::Boolean(v) => vec![*v as u8],
+            Value::Byte(v) => vec![*v],
+            Value::Int16(v) => v.to_le_bytes().to_vec(),
+            Value::Uint16(v) => v.to_le_bytes().to_vec(),
+            Value::Int32(v) | Value::Handle(v) => v.to_le_bytes().to_vec(),
+            Value::Uint32(v) => v.to_le_bytes().to_vec(),
+            Value::Int64(v) => v.to_le_bytes().to_vec(),
+            Value::Uint64(v) => v.to_le_bytes().to_vec(),
+            Value::Double(v) => v.to_le_bytes().to_vec(),
+            Value::String(s) | Value::ObjectPath(s) | Value::Signature(s) => {
+                let mut out = s.as_bytes().to_vec();
+                out.push(0);
+                out
+            }
+            Value::Variant(value) => {
+                let mut out = value.serialize();
+                out.push(0);
+                out.extend_from_slice(value.type_string().as_bytes());
+                out
+            }
+            Value::Maybe(ty, value) => match value {
+                Some(value) => {
+                    let mut out = value.serialize();
+                    if fixed_size(ty).is_none() {
+                        out.push(0);
+                    }
+                    out
+                }
+                None => Vec::new(),
+            },
+            Value::Array(ty, values) => {
+                let mut out = Vec::new();
+                if fixed_size(ty).is_some() {
+                    for value in values {
+                        out.extend(value.serialize());
+                    }
+                    return out;
+                }
+
+                let mut offsets = Vec::new();
+                for value in values {
+                    pad(&mut out, alignment(ty));
+                    out.extend(value.serialize());
+                    offsets.push(out.len());
+                }
+                write_offsets(&mut out, &offsets);
+                out
+            }
+            Value::Tuple(values) => serialize_tuple(&self.type_string(), values),
+            Value::DictEntry(key, value) => {
+                serialize_tuple(&self.type_string(), &[(**key).clone(), (**value).clone()])
+            }
+        }
+    }
+}
+
+fn serialize_tuple(ty: &str, values: &[Value]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut offsets = Vec::new();
+
+    for (i, value) in values.iter().enumerate() {
+        let member = value.type_string();
+        pad(&mut out, alignment(&member));
+        out.extend(value.serialize());
+        if fixed_size(&member).is_none() && i + 1 != values.len() {
+            offsets.push(out.len());
+        }
+    }
+
+    if fixed_size(ty).is_some() {
+        pad(&mut out, alignment(ty));
+        if out.is_empty() {
+            out.push(0);
+        }
+        return out;
+    }
+
+    // Tuple offsets are stored last member first
+    offsets.reverse();
+    write_offsets(&mut out, &offsets);
+    out
+}
+
+// Framing offsets use the smallest integer size
+// that can address the whole container.
+fn write_offsets(out: &mut Vec<u8>, offsets: &[usize]) {
+    let body = out.len();
+    let size = if offsets.is_empty() {
+        0
+    } else if body + offsets.len() <= 0xff {
+        1
+    } else if body + 2 * offsets.len() <= 0xffff {
+        2
+    } else if body + 4 * offsets.len() <= 0xffff_ffff {
+        4
+    } else {
+        8
+    };
+
+    for offset in offsets {
+        out.extend_from_slice(&(*offset as u64).to_le_bytes()[..size]);
+    }
+}
+
+fn pad(out: &mut Vec<u8>, alignment: usize) {
+    let padding = out.len().wrapping_neg() & (alignment - 1);
+    out.resize(out.len() + padding, 0);
+}
+
+// Split the first complete type off a type string,
+// e.g. "a{sv}i" into "a{sv}" and "i"
+pub fn split_type(ty: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in ty.char_indices() {
+        match c {
+            'a' | 'm' => continue,
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            'b' | 'y' | 'n' | 'q' | 'i' | 'u' | 'x' | 't' | 'h' | 'd' | 's' | 'o' | 'g' | 'v' => {}
+            _ => return None,
+        }
+        if depth < 0 {
+            return None;
+        }
+        if depth == 0 {
+            return Some((&ty[..=i], &ty[i + 1..]));
+        }
+    }
+    None
+}
+
+// Members of a tuple or dict entry type
+fn members(ty: &str) -> Option<Vec<&str>> {
+    let mut rest = &ty[1..ty.len() - 1];
+    let mut members = Vec::new();
+    while !rest.is_empty() {
+        let (member, tail) = split_type(rest)?;
+        members.push(member);
+        rest = tail;
+    }
+    Some(members)
+}
+
+// A single complete and definite type
+pub fn is_valid_type(ty: &str) -> bool {
+    fn check(ty: &str) -> bool {
+        match ty.chars().next() {
+            Some('a') | Some('m') => check(&ty[1..]),
+            Some('(') => {
+                ty.ends_with(')')
+                    && members(ty).is_some_and(|members| members.iter().all(|m| check(m)))
+            }
+            Some('{') => {
+                ty.ends_with('}')
+                    && members(ty).is_some_and(|members| {
+                        members.len() == 2 && is_basic(members[0]) && check(members[1])
+                    })
+            }
+            Some(_) => is_basic(ty) || ty == "v",
+            None => false,
+        }
+    }
+    matches!(split_type(ty), Some((_, ""))) && check(ty)
+}
+
+fn is_basic(ty: &str) -> bool {
+    ty.len() == 1 && "bynqiuxthdsog".contains(ty)
+}
+
+pub fn alignment(ty: &str) -> usize {
+    match ty.chars().next() {
+        Some('n') | Some('q') => 2,
+        Some('i') | Some('u') | Some('h') => 4,
+        Some('x') | Some('t') | Some('d') | Some('v') => 8,
+        Some('a') | Some('m') => alignment(&ty[1..]),
+        Some('(') | Some('{') => members(ty)
+            .unwrap_or_default()
+            .iter()
+            .map(|member| alignment(member))
+            .max()
+            .unwrap_or(1),
+        _ => 1,
+    }
+}
+
+pub fn fixed_size(ty: &str) -> Option<usize> {
+    match ty.chars().next()? {
+        'b' | 'y' => Some(1),
+        'n' | 'q' => Some(2),
+        'i' | 'u' | 'h' => Some(4),
+        'x' | 't' | 'd' => Some(8),
+        '(' | '{' => {
+            let mut size = 0usize;
+            for member in members(ty)? {
+                let align = alignment(member);
+                size = size.div_ceil(align) * align + fixed_size(member)?;
+            }
+            let align = alignment(ty);
+            Some(if size == 0 {
+                1
+            } else {
+                size.div_ceil(align) * align
+            })
+        }
+        _ => None,
+    }
+}
+
+// Parse text of the given type, e.g. parse("as", "['a', 'b']")
+pub fn parse(ty: &str, text: &str) -> Result<Value, String> {
+    if !is_valid_type(ty) {
+        return Err(format!("invalid type \"{}\"", ty));
+    }
+
+    let mut parser = Parser { text, pos: 0 };
+    let value = parser.value(ty)?;
+    parser.whitespace();
+    if parser.pos != text.len() {
+        return Err(format!(
+            "unexpected \"{}\" after value",
+            &text[parser.pos..]
+        ));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected \"{}\" at \"{}\"", token, self.rest()))
+        }
+    }
+
+    // Identifier or number-ish token
+    fn word(&mut self) -> &'a str {
+        self.whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "+-._".contains(c)))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    // Skip type annotations like "@as" or "uint32"
+    fn annotation(&mut self, ty: &str) -> Result<(), String> {
+        self.whitespace();
+        if self.rest().starts_with('@') {
+            self.pos += 1;
+            let rest = self.rest();
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '[' || c == '<')
+                .unwrap_or(rest.len());
+            self.pos += len;
+            if &rest[..len] != ty {
+                return Err(format!(
+                    "type annotation @{} doesn't match {}",
+                    &rest[..len],
+                    ty
+                ));
+            }
+            return Ok(());
+        }
+
+        let keywords = [
+            ("boolean", "b"),
+            ("byte", "y"),
+            ("int16", "n"),
+            ("uint16", "q"),
+            ("int32", "i"),
+            ("uint32", "u"),
+            ("int64", "x"),
+            ("uint64", "t"),
+            ("handle", "h"),
+            ("double", "d"),
+            ("objectpath", "o"),
+            ("signature", "g"),
+        ];
+        for (keyword, keyword_ty) in keywords.iter() {
+            let rest = self.rest();
+            if rest.starts_with(keyword)
+                && rest[keyword.len()..].starts_with(|c: char| c.is_whitespace())
+            {
+                if *keyword_ty != ty {
+                    return Err(format!("{} isn't of type {}", keyword, ty));
+                }
+                self.pos += keyword.len();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, ty: &str) -> Result<Value, String> {
+        self.annotation(ty)?;
+        self.whitespace();
+
+        let first = ty.chars().next().unwrap_or_default();
+        match first {
+            'b' => match self.word() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                word => Err(format!("\"{}\" isn't a boolean", word)),
+            },
+            'y' => Ok(Value::Byte(self.integer()?)),
+            'n' => Ok(Value::Int16(self.integer()?)),
+            'q' => Ok(Value::Uint16(self.integer()?)),
+            'i' => Ok(Value::Int32(self.integer()?)),
+            'u' => Ok(Value::Uint32(self.integer()?)),
+            'x' => Ok(Value::Int64(self.integer()?)),
+            't' => Ok(Value::Uint64(self.integer()?)),
+            'h' => Ok(Value::Handle(self.integer()?)),
+            'd' => {
+                let word = self.word();
+                word.parse()
+                    .map(Value::Double)
+                    .map_err(|_| format!("\"{}\" isn't a double", word))
+            }
+            's' => Ok(Value::String(self.string()?)),
+            'o' => {
+                let path = self.string()?;
+                let valid = path == "/"
+                    || (path.starts_with('/')
+                        && !path.ends_with('/')
+                        && path[1..].split('/').all(|part| {
+                            !part.is_empty()
+                                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                        }));
+                if valid {
+                    Ok(Value::ObjectPath(path))
+                } else {
+                    Err(format!("'{}' isn't a valid object path", path))
+                }
+            }
+            'g' => {
+                let signature = self.string()?;
+                let mut rest = signature.as_str();
+                while let Some((_, tail)) = split_type(rest) {
+                    rest = tail;
+                }
+                if rest.is_empty() {
+                    Ok(Value::Signature(signature))
+                } else {
+                    Err(format!("'{}' isn't a valid signature", signature))
+                }
+            }
+            'v' => {
+                self.expect("<")?;
+                let value = self.inferred()?;
+                self.expect(">")?;
+                Ok(Value::Variant(Box::new(value)))
+            }
+            'm' => {
+                let element = &ty[1..];
+                if self.eat("nothing") {
+                    Ok(Value::Maybe(element.to_string(), None))
+                } else {
+                    self.eat("just");
+                    let value = self.value(element)?;
+                    Ok(Value::Maybe(element.to_string(), Some(Box::new(value))))
+                }
+            }
+            'a' => self.array(&ty[1..]),
+            '(' => {
+                let members = members(ty).unwrap_or_default();
+                self.expect("(")?;
+                let mut values = Vec::new();
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        self.expect(",")?;
+                    }
+                    values.push(self.value(member)?);
+                }
+                if members.len() == 1 {
+                    self.expect(",")?;
+                } else {
+                    self.eat(",");
+                }
+                self.expect(")")?;
+                Ok(Value::Tuple(values))
+            }
+            '{' => {
+                let members = members(ty).unwrap_or_default();
+                self.expect("{")?;
+                let key = self.value(members[0])?;
+                self.expect(",")?;
+                let value = self.value(members[1])?;
+                self.expect("}")?;
+                Ok(Value::DictEntry(Box::new(key), Box::new(value)))
+            }
+            _ => Err(format!("unsupported type \"{}\"", ty)),
+        }
+    }
+
+    fn array(&mut self, element: &str) -> Result<Value, String> {
+        // Bytestring b'foo' for ay
+        if element == "y" && (self.rest().starts_with("b'") || self.rest().starts_with("b\"")) {
+            self.pos += 1;
+            let bytes = self.string()?.bytes().map(Value::Byte).collect();
+            return Ok(Value::Array(element.to_string(), bytes));
+        }
+
+        let mut values = Vec::new();
+        if element.starts_with('{') && self.eat("{") {
+            // Dictionary {key: value, ...}
+            let members = members(element).unwrap_or_default();
+            while !self.eat("}") {
+                if !values.is_empty() {
+                    self.expect(",")?;
+                }
+                let key = self.value(members[0])?;
+                self.expect(":")?;
+                let value = self.value(members[1])?;
+                values.push(Value::DictEntry(Box::new(key), Box::new(value)));
+            }
+        } else {
+            self.expect("[")?;
+            while !self.eat("]") {
+                if !values.is_empty() {
+                    self.expect(",")?;
+                }
+                values.push(self.value(element)?);
+            }
+        }
+        Ok(Value::Array(element.to_string(), values))
+    }
+
+    // Value of a variant, type is taken from
+    // an annotation or guessed from the text.
+    fn inferred(&mut self) -> Result<Value, String> {
+        self.whitespace();
+        let rest = self.rest();
+        if rest.starts_with('@') {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '[' || c == '<')
+                .unwrap_or(rest.len());
+            let ty = rest[1..len].to_string();
+            return self.value(&ty);
+        }
+
+        let ty = if rest.starts_with("true") || rest.starts_with("false") {
+            "b"
+        } else if rest.starts_with('\'') || rest.starts_with('"') {
+            "s"
+        } else if rest.starts_with('<') {
+            "v"
+        } else if rest.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+                .unwrap_or(rest.len());
+            let number = &rest[..len];
+            if !number.starts_with("0x") && number.contains(['.', 'e']) {
+                "d"
+            } else {
+                "i"
+            }
+        } else {
+            return Err(format!("can't infer type of \"{}\", use @type", rest));
+        };
+        self.value(ty)
+    }
+
+    fn integer<T: std::convert::TryFrom<i128>>(&mut self) -> Result<T, String> {
+        let word = self.word();
+        let (negative, digits) = match word.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, word.trim_start_matches('+')),
+        };
+        let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+            i128::from_str_radix(hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            i128::from_str_radix(&digits[1..], 8)
+        } else {
+            digits.parse()
+        };
+
+        parsed
+            .ok()
+            .map(|value| if negative { -value } else { value })
+            .and_then(|value| T::try_from(value).ok())
+            .ok_or_else(|| format!("\"{}\" isn't a valid number or is out of range", word))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.whitespace();
+        let mut chars = self.rest().char_indices();
+        let quote = match chars.next() {
+            Some((_, c)) if c == '\'' || c == '"' => c,
+            _ => return Err(format!("expected a string at \"{}\"", self.rest())),
+        };
+
+        let mut out = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('u') => {
+                        let hex = (0..4)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<String>();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(std::char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{}", hex))?;
+                        out.push(c);
+                    }
+                    Some(c) => out.push(c),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn parses_maybe_types() {
+        assert_eq!(
+            parse("ms", "nothing"),
+            Ok(Value::Maybe("s".to_string(), None))
+        );
+        let just = Ok(Value::Maybe("s".to_string(), Some(Box::new(string("a")))));
+        assert_eq!(parse("ms", "'a'"), just);
+        assert_eq!(parse("ms", "just 'a'"), just);
+        assert_eq!(
+            parse("mi", "5"),
+            Ok(Value::Maybe(
+                "i".to_string(),
+                Some(Box::new(Value::Int32(5)))
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_arrays() {
+        assert_eq!(
+            parse("as", "['a', \"b\"]"),
+            Ok(Value::Array(
+                "s".to_string(),
+                vec![string("a"), string("b")]
+            ))
+        );
+        assert_eq!(
+            parse("as", "@as []"),
+            Ok(Value::Array("s".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            parse("ay", "b'hi'"),
+            Ok(Value::Array(
+                "y".to_string(),
+                vec![Value::Byte(b'h'), Value::Byte(b'i')]
+            ))
+        );
+        assert_eq!(
+            parse("aai", "[[1], []]"),
+            Ok(Value::Array(
+                "ai".to_string(),
+                vec![
+                    Value::Array("i".to_string(), vec![Value::Int32(1)]),
+                    Value::Array("i".to_string(), Vec::new()),
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_dicts() {
+        assert_eq!(
+            parse("a{sv}", "{'a': <1>, 'b': <'x'>}"),
+            Ok(Value::Array(
+                "{sv}".to_string(),
+                vec![
+                    Value::DictEntry(
+                        Box::new(string("a")),
+                        Box::new(Value::Variant(Box::new(Value::Int32(1))))
+                    ),
+                    Value::DictEntry(
+                        Box::new(string("b")),
+                        Box::new(Value::Variant(Box::new(string("x"))))
+                    ),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse("a{si}", "{}"),
+            Ok(Value::Array("{si}".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            parse("{si}", "{'a', 1}"),
+            Ok(Value::DictEntry(
+                Box::new(string("a")),
+                Box::new(Value::Int32(1))
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_tuples_and_numbers() {
+        assert_eq!(
+            parse("(is)", "(1, 'a')"),
+            Ok(Value::Tuple(vec![Value::Int32(1), string("a")]))
+        );
+        // One member tuples need the trailing comma
+        assert!(parse("(i)", "(1)").is_err());
+        assert_eq!(
+            parse("(i)", "(1,)"),
+            Ok(Value::Tuple(vec![Value::Int32(1)]))
+        );
+        assert_eq!(parse("i", "0x10"), Ok(Value::Int32(16)));
+        assert_eq!(parse("i", "010"), Ok(Value::Int32(8)));
+        assert_eq!(parse("i", "-5"), Ok(Value::Int32(-5)));
+        assert_eq!(parse("u", "uint32 7"), Ok(Value::Uint32(7)));
+        assert_eq!(parse("d", "1.5"), Ok(Value::Double(1.5)));
+        assert_eq!(parse("s", r"'it\'s\n'"), Ok(string("it's\n")));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(parse("y", "256").is_err());
+        assert!(parse("q", "-1").is_err());
+        assert!(parse("i", "1.5").is_err());
+        assert!(parse("b", "yes").is_err());
+        assert!(parse("s", "'abc").is_err());
+        assert!(parse("as", "['a' 'b']").is_err());
+        assert!(parse("i", "1 2").is_err());
+        assert!(parse("i", "@s 'a'").is_err());
+        assert!(parse("i", "int64 1").is_err());
+        assert!(parse("o", "'/a/'").is_err());
+        assert!(parse("v", "<[]>").is_err());
+        // Not a single complete type
+        assert!(parse("a", "[]").is_err());
+        assert!(parse("ii", "1").is_err());
+        assert!(parse("{asi}", "{[], 1}").is_err());
+    }
+
+    #[test]
+    fn validates_types() {
+        for ty in &["s", "as", "a{sv}", "(ias)", "mmi", "()", "v"] {
+            assert!(is_valid_type(ty), "{}", ty);
+        }
+        for ty in &["", "a", "(i", "i)", "{vs}", "{sss}", "z", "ss"] {
+            assert!(!is_valid_type(ty), "{}", ty);
+        }
+        assert_eq!(split_type("a{sv}i"), Some(("a{sv}", "i")));
+        assert_eq!(fixed_size("(yi)"), Some(8));
+        assert_eq!(fixed_size("(iy)"), Some(8));
+        assert_eq!(fixed_size("()"), Some(1));
+        assert_eq!(fixed_size("(is)"), None);
+        assert_eq!(alignment("(yx)"), 8);
+    }
+
+    #[test]
+    fn serializes_like_glib() {
+        let bytes = |ty: &str, text: &str| parse(ty, text).unwrap().serialize();
+        assert_eq!(bytes("i", "1"), [1, 0, 0, 0]);
+        assert_eq!(bytes("s", "'ab'"), *b"ab\0");
+        // Offsets of every element follow the elements
+        assert_eq!(bytes("as", "['a', 'bc']"), *b"a\0bc\0\x02\x05");
+        assert_eq!(bytes("ai", "[1, 2]"), [1, 0, 0, 0, 2, 0, 0, 0]);
+        // The last member of a tuple has no offset
+        assert_eq!(bytes("(is)", "(1, 'a')"), *b"\x01\0\0\0a\0");
+        assert_eq!(bytes("(si)", "('a', 1)"), *b"a\0\0\0\x01\0\0\0\x02");
+        assert_eq!(bytes("(yi)", "(1, 2)"), [1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(bytes("()", "()"), [0]);
+        // Non fixed size maybes get an extra nul
+        assert_eq!(bytes("ms", "'a'"), *b"a\0\0");
+        assert_eq!(bytes("mi", "5"), [5, 0, 0, 0]);
+        assert!(bytes("ms", "nothing").is_empty());
+        assert_eq!(bytes("v", "<true>"), *b"\x01\0b");
+        assert_eq!(
+            bytes("a{sv}", "{'a': <1>}"),
+            *b"a\0\0\0\0\0\0\0\x01\0\0\0\0i\x02\x0f"
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+// Writer for GVDB, the hash table file format
+// used by .gresource bundles.
+//
+// Layout (all integers little endian):
+// header  "GVariant", version, options, root pointer
+// table   bloom header, bucket offsets, items
+// items   hash, parent, key start/size, type, value pointer
+// followed by keys, values and child lists.
+
+struct Item {
+    key: String,
+    parent: Option<usize>,
+    // Serialized GVariant of type "v"
+    value: Option<Vec<u8>>,
+    table: Option<HashTable>,
+    children: Vec<usize>,
+}
+
+#[derive(Default)]
+pub struct HashTable {
+    items: Vec<Item>,
+    index: HashMap<String, usize>,
+}
+
+impl HashTable {
+    pub fn get(&self, key: &str) -> Option<usize> {
+        self.index.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: &str, value: Option<Vec<u8>>) -> usize {
+        let item = Item {
+            key: key.to_string(),
+            parent: None,
+            value,
+            table: None,
+            children: Vec::new(),
+        };
+        match self.index.get(key) {
+            Some(&index) => {
+                self.items[index] = item;
+                index
+            }
+            None => {
+                self.items.push(item);
+                self.index.insert(key.to_string(), self.items.len() - 1);
+                self.items.len() - 1
+            }
+        }
+    }
+
+    // Nested table, e.g. a schema in gschemas.compiled
+    pub fn insert_table(&mut self, key: &str, table: HashTable) -> usize {
+        let item = self.insert(key, None);
+        self.items[item].table = Some(table);
+        item
+    }
+
+    // Parent's key must be a prefix of item's key,
+    // only the rest of the key gets stored.
+    pub fn set_parent(&mut self, item: usize, parent: usize) {
+        self.items[item].parent = Some(parent);
+
+        let mut children = std::mem::take(&mut self.items[parent].children);
+        children.push(item);
+        children.sort_by(|a, b| self.items[*a].key.cmp(&self.items[*b].key));
+        children.dedup();
+        self.items[parent].children = children;
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![0u8; 24];
+        let (start, end) = self.write(&mut out);
+
+        out[..8].copy_from_slice(b"GVariant");
+        put_u32(&mut out, 16, start as u32);
+        put_u32(&mut out, 20, end as u32);
+        out
+    }
+
+    // Append the table followed by its keys and
+    // values to out, returns where the table is.
+    fn write(&self, out: &mut Vec<u8>) -> (usize, usize) {
+        let n_items = self.items.len();
+        let n_buckets = n_items as u32;
+
+        // Items are stored grouped by bucket
+        let hashes = self
+            .items
+            .iter()
+            .map(|item| djb_hash(&item.key))
+            .collect::<Vec<_>>();
+        let mut order = (0..n_items).collect::<Vec<_>>();
+        order.sort_by_key(|&index| hashes[index] % n_buckets);
+
+        let mut assigned = vec![0u32; n_items];
+        for (position, &index) in order.iter().enumerate() {
+            assigned[index] = position as u32;
+        }
+
+        // Offset of the first item in every bucket
+        let buckets = (0..n_buckets)
+            .map(|bucket| {
+                order
+                    .iter()
+                    .position(|&index| hashes[index] % n_buckets >= bucket)
+                    .unwrap_or(n_items) as u32
+            })
+            .collect::<Vec<_>>();
+
+        align(out, 4);
+        let table_start = out.len();
+        let table_size = 8 + 4 * n_items + 24 * n_items;
+        out.resize(table_start + table_size, 0);
+
+        put_u32(out, table_start, 5 << 27);
+        put_u32(out, table_start + 4, n_buckets);
+        for (bucket, offset) in buckets.iter().enumerate() {
+            put_u32(out, table_start + 8 + 4 * bucket, *offset);
+        }
+
+        for (position, &index) in order.iter().enumerate() {
+            let item = &self.items[index];
+            let entry = table_start + 8 + 4 * n_items + 24 * position;
+
+            let (parent, basename) = match item.parent {
+                Some(parent) => (assigned[parent], &item.key[self.items[parent].key.len()..]),
+                None => (u32::MAX, &item.key[..]),
+            };
+
+            let key_start = out.len();
+            out.extend_from_slice(basename.as_bytes());
+
+            let (kind, start, end) = if let Some(value) = &item.value {
+                align(out, 8);
+                let start = out.len();
+                out.extend_from_slice(value);
+                (b'v', start, out.len())
+            } else if let Some(table) = &item.table {
+                let (start, end) = table.write(out);
+                (b'H', start, end)
+            } else if !item.children.is_empty() {
+                align(out, 4);
+                let start = out.len();
+                for child in &item.children {
+                    out.extend_from_slice(&assigned[*child].to_le_bytes());
+                }
+                (b'L', start, out.len())
+            } else {
+                (0, 0, 0)
+            };
+
+            put_u32(out, entry, hashes[index]);
+            put_u32(out, entry + 4, parent);
+            put_u32(out, entry + 8, key_start as u32);
+            out[entry + 12..entry + 14].copy_from_slice(&(basename.len() as u16).to_le_bytes());
+            out[entry + 14] = kind;
+            put_u32(out, entry + 16, start as u32);
+            put_u32(out, entry + 20, end as u32);
+        }
+
+        (table_start, table_start + table_size)
+    }
+}
+
+// Same as gvdb's djb_hash, which hashes signed chars
+fn djb_hash(key: &str) -> u32 {
+    key.bytes().fold(5381u32, |hash, byte| {
+        hash.wrapping_mul(33).wrapping_add(byte as i8 as i32 as u32)
+    })
+}
+
+fn put_u32(out: &mut [u8], offset: usize, value: u32) {
+    out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn align(out: &mut Vec<u8>, alignment: usize) {
+    let padding = out.len().wrapping_neg() & (alignment - 1);
+    out.resize(out.len() + padding, 0);
+}
+
+// Lookups the way gvdb's reader does them, for checking what was written
+#[cfg(test)]
+pub mod reader {
+    use super::djb_hash;
+
+    pub enum Entry<'a> {
+        Value(&'a [u8]),
+        Table(Table<'a>),
+        // Full keys of the children
+        List(Vec<String>),
+        Empty,
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct Table<'a> {
+        data: &'a [u8],
+        start: usize,
+        end: usize,
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize
+    }
+
+    impl<'a> Table<'a> {
+        pub fn root(data: &'a [u8]) -> Table<'a> {
+            assert_eq!(&data[..8], b"GVariant");
+            Table {
+                data,
+                start: u32_at(data, 16),
+                end: u32_at(data, 20),
+            }
+        }
+
+        fn n_bloom(&self) -> usize {
+            u32_at(self.data, self.start) & ((1 << 27) - 1)
+        }
+
+        fn n_buckets(&self) -> usize {
+            u32_at(self.data, self.start + 4)
+        }
+
+        fn items(&self) -> usize {
+            self.start + 8 + 4 * (self.n_bloom() + self.n_buckets())
+        }
+
+        fn n_items(&self) -> usize {
+            (self.end - self.items()) / 24
+        }
+
+        fn item(&self, index: usize) -> usize {
+            self.items() + 24 * index
+        }
+
+        // Basename appended to the parents' keys
+        fn key(&self, index: usize) -> String {
+            let item = self.item(index);
+            let start = u32_at(self.data, item + 8);
+            let size = u16::from_le_bytes([self.data[item + 12], self.data[item + 13]]) as usize;
+            let basename = std::str::from_utf8(&self.data[start..start + size]).unwrap();
+            match u32_at(self.data, item + 4) {
+                0xffff_ffff => basename.to_string(),
+                parent => self.key(parent) + basename,
+            }
+        }
+
+        pub fn keys(&self) -> Vec<String> {
+            (0..self.n_items()).map(|index| self.key(index)).collect()
+        }
+
+        pub fn lookup(&self, key: &str) -> Option<Entry<'a>> {
+            let hash = djb_hash(key);
+            let n_buckets = self.n_buckets();
+            if n_buckets == 0 {
+                return None;
+            }
+            let bucket = hash as usize % n_buckets;
+            let buckets = self.start + 8 + 4 * self.n_bloom();
+            let first = u32_at(self.data, buckets + 4 * bucket);
+            let last = if bucket + 1 < n_buckets {
+                u32_at(self.data, buckets + 4 * (bucket + 1))
+            } else {
+                self.n_items()
+            };
+
+            let index = (first..last).find(|&index| {
+                u32_at(self.data, self.item(index)) == hash as usize && self.key(index) == key
+            })?;
+            let item = self.item(index);
+            let (start, end) = (u32_at(self.data, item + 16), u32_at(self.data, item + 20));
+            Some(match self.data[item + 14] {
+                b'v' => Entry::Value(&self.data[start..end]),
+                b'H' => Entry::Table(Table {
+                    data: self.data,
+                    start,
+                    end,
+                }),
+                b'L' => Entry::List(
+                    (start..end)
+                        .step_by(4)
+                        .map(|offset| self.key(u32_at(self.data, offset)))
+                        .collect(),
+                ),
+                _ => Entry::Empty,
+            })
+        }
+
+        pub fn value(&self, key: &str) -> Option<&'a [u8]> {
+            match self.lookup(key)? {
+                Entry::Value(value) => Some(value),
+                _ => None,
+            }
+        }
+
+        pub fn table(&self, key: &str) -> Option<Table<'a>> {
+            match self.lookup(key)? {
+                Entry::Table(table) => Some(table),
+                _ => None,
+            }
+        }
+
+        pub fn list(&self, key: &str) -> Option<Vec<String>> {
+            match self.lookup(key)? {
+                Entry::List(list) => Some(list),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reader::Table;
+    use super::*;
+
+    #[test]
+    fn empty_table_reads_back() {
+        let data = HashTable::default().serialize();
+        assert_eq!(&data[..8], b"GVariant");
+        let root = Table::root(&data);
+        assert!(root.keys().is_empty());
+        assert!(root.lookup("").is_none());
+    }
+
+    #[test]
+    fn values_read_back() {
+        let mut table = HashTable::default();
+        for i in 0..100 {
+            table.insert(&format!("key{}", i), Some(vec![i as u8; i % 7]));
+        }
+        // Replacing a key keeps a single item
+        table.insert("key5", Some(b"five".to_vec()));
+
+        let data = table.serialize();
+        let root = Table::root(&data);
+        assert_eq!(root.keys().len(), 100);
+        for i in (0..100).filter(|&i| i != 5) {
+            let value = root.value(&format!("key{}", i)).unwrap();
+            assert_eq!(value, &vec![i as u8; i % 7][..], "key{}", i);
+        }
+        assert_eq!(root.value("key5").unwrap(), b"five");
+        assert!(root.lookup("key100").is_none());
+        assert!(root.lookup("key").is_none());
+    }
+
+    #[test]
+    fn values_are_aligned() {
+        let mut table = HashTable::default();
+        table.insert("a", Some(b"x".to_vec()));
+        table.insert("bcd", Some(b"y".to_vec()));
+        let data = table.serialize();
+        for key in &["a", "bcd"] {
+            let value = Table::root(&data).value(key).unwrap();
+            assert_eq!((value.as_ptr() as usize - data.as_ptr() as usize) % 8, 0);
+        }
+    }
+
+    #[test]
+    fn parents_and_tables_read_back() {
+        let mut nested = HashTable::default();
+        nested.insert("inner", Some(b"1".to_vec()));
+
+        let mut table = HashTable::default();
+        let root = table.insert("/", None);
+        let dir = table.insert("/dir/", None);
+        table.set_parent(dir, root);
+        for name in &["/dir/b", "/dir/a", "/dir/\u{e9}"] {
+            let item = table.insert(name, Some(name.as_bytes().to_vec()));
+            table.set_parent(item, dir);
+        }
+        table.insert_table("nested", nested);
+
+        let data = table.serialize();
+        let root = Table::root(&data);
+        let mut keys = root.keys();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["/", "/dir/", "/dir/a", "/dir/b", "/dir/\u{e9}", "nested"]
+        );
+        // Children are sorted and only store the rest of their key
+        assert_eq!(root.list("/").unwrap(), ["/dir/"]);
+        assert_eq!(
+            root.list("/dir/").unwrap(),
+            ["/dir/a", "/dir/b", "/dir/\u{e9}"]
+        );
+        assert_eq!(root.value("/dir/\u{e9}").unwrap(), "/dir/\u{e9}".as_bytes());
+        assert!(root.value("/dir/").is_none());
+
+        let nested = root.table("nested").unwrap();
+        assert_eq!(nested.keys(), ["inner"]);
+        assert_eq!(nested.value("inner").unwrap(), b"1");
+    }
+
+    #[test]
+    fn hashes_like_gvdb() {
+        assert_eq!(djb_hash(""), 5381);
+        assert_eq!(djb_hash("a"), 5381 * 33 + 97);
+        // Bytes above 0x7f count as negative
+        assert_eq!(djb_hash("\u{e9}"), (5381u32 * 33 - 0x3d) * 33 - 0x57);
+    }
+}
@@ -0,0 +1,27 @@
+// Packaging steps behind the cargo-pkg CLI, for scripting
+// packaging from an xtask or the app's build.rs.
+mod blueprint;
+mod builder;
+pub mod buildscript;
+mod commands;
+mod fingerprint;
+mod gettext;
+mod gresource;
+mod gschema;
+mod gvariant;
+mod gvdb;
+mod metadata;
+mod mo;
+mod po;
+mod pseudo;
+pub mod runtime;
+mod scss;
+mod settings;
+mod translate;
+// inotify based, only `cargo pkg watch` needs it
+#[cfg(target_os = "linux")]
+mod watch;
+
+pub use builder::{Builder, PlannedStep, Step};
+pub use metadata::{Hook, Metadata};
+pub use runtime::Launch;
@@ -1,37 +1,34 @@
-use std::{
-    collections::HashMap, env, ffi::OsStr, fs::read_to_string, path::PathBuf, process::Command,
-};
-
-use toml::{from_str, value::Value};
-
-mod commands;
-mod metadata;
-use commands::Commands;
-use metadata::Metadata;
-
-#[macro_export]
-macro_rules! isexists {
-    ($str: tt) => {
-        if !std::process::Command::new($str).output().is_ok() {
-            println!("{} not found!", $str);
-            std::process::exit(-1);
-        }
-    };
-}
+use cargo_pkg::{runtime, Builder, Launch, Metadata};
+use std::{env, path::PathBuf, process::Command};
 
 fn help() {
-    println!("Usage: cargo pkg [ACTION] [OPTION] DIR");
+    println!("Usage: cargo pkg [ACTION] [OPTION] DIR [-- APP ARGS]");
     std::process::exit(-1);
 }
 
-fn main() {
-    // Check build dependent packages exists
-    // throw error & exit if not.
-    isexists!("msgfmt");
-    isexists!("glib-compile-resources");
-    isexists!("glib-compile-schemas");
+// The value of --min-percent
+fn percent(value: Option<&String>) -> u32 {
+    match value.and_then(|value| value.parse().ok()) {
+        Some(percent) if percent <= 100 => percent,
+        _ => {
+            println!("--min-percent needs a number from 0 to 100");
+            help();
+            0
+        }
+    }
+}
 
-    let args = env::args().filter(|s| s != "pkg").collect::<Vec<_>>();
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    // cargo passes the subcommand name first
+    if args.get(1) == Some(&"pkg".to_owned()) {
+        args.remove(1);
+    }
+    // Everything after -- goes to the app
+    let appargs = match args.iter().position(|arg| arg == "--") {
+        Some(i) => args.split_off(i)[1..].to_vec(),
+        None => Vec::new(),
+    };
 
     if args.get(1) == Some(&"new".to_owned())
         && args.get(2) == Some(&"-id".to_owned())
@@ -41,7 +38,7 @@ fn main() {
         && args.get(6).is_some()
     {
         let chars = args[3].matches('.').count();
-        if chars == 2 && args[3].chars().last() != Some('.') {
+        if chars == 2 && !args[3].ends_with('.') {
             Builder::create_project(&args[3], &args[5], &args[6]);
             println!(
                 "Created \"{}\" with application id \"{}\"",
@@ -50,10 +47,46 @@ fn main() {
         } else {
             println!("App ID must follow this pattern `io.foo.Bar`");
         }
-    } else if (args.get(1) == Some(&"run".to_owned()) || args.get(1) == Some(&"install".to_owned()))
+    } else if (args.get(1) == Some(&"run".to_owned())
+        || args.get(1) == Some(&"install".to_owned())
+        || args.get(1) == Some(&"watch".to_owned()))
         && args.len() > 2
     {
-        let buildflags = &args[2..args.len() - 1];
+        // These flags are ours, everything else goes to cargo install
+        let flags = &args[2..args.len() - 1];
+        let force = flags.contains(&"--force-rebuild".to_owned());
+        let pseudo_locale = flags.contains(&"--pseudo-locale".to_owned());
+        // --skip NAME, repeatable or comma separated
+        let mut skip = Vec::new();
+        let mut min_percent = 0;
+        let mut rest = Vec::new();
+        let mut iter = flags
+            .iter()
+            .filter(|flag| *flag != "--force-rebuild" && *flag != "--pseudo-locale");
+        while let Some(flag) = iter.next() {
+            if flag == "--skip" {
+                match iter.next() {
+                    Some(names) => skip.extend(names.split(',').map(str::to_string)),
+                    None => {
+                        println!("--skip needs a step name");
+                        help();
+                    }
+                }
+            } else if flag == "--min-percent" {
+                min_percent = percent(iter.next());
+            } else {
+                rest.push(flag.clone());
+            }
+        }
+        let flags = rest;
+        let (launch, buildflags) = match Launch::parse(&flags) {
+            Some(parsed) => parsed,
+            None => {
+                println!("--env needs KEY=VALUE, --log a filter and --lang a language");
+                help();
+                return;
+            }
+        };
         // println!("Building with agrs {}", buildflags.join(" "));
 
         let profile = if buildflags.contains(&"--debug".to_owned()) {
@@ -68,306 +101,158 @@ fn main() {
         });
 
         let metadata = Metadata::from("Cargo.toml").expect("Error parsing Cargo.toml");
-        let builder = Builder::new(&args[2..args.len() - 1], profile);
-
-        let issuccess = builder.build(&metadata, &prefix);
-
-        if args.get(1) == Some(&"run".to_owned()) && issuccess {
-            Command::new(prefix.join("bin").join(&metadata.bin).to_str().unwrap())
-                .status()
-                .ok();
+        let mut builder = Builder::new(&buildflags, profile, force);
+        builder.min_percent = min_percent;
+        builder.pseudo_locale = pseudo_locale;
+        builder.add_hooks(&metadata.hooks);
+        for name in &skip {
+            if !builder.skip(name) {
+                let names = builder.steps.iter().map(|step| step.name());
+                println!(
+                    "Unknown step \"{}\", steps are: {}",
+                    name,
+                    names.collect::<Vec<_>>().join(", ")
+                );
+                help();
+            }
         }
-    } else {
-        println!("Invalid arguments");
-        std::process::exit(-1);
-    }
-}
 
-struct Builder<'a> {
-    // Profile can be either "release" or "debug"
-    profile: &'a str,
-    buildflags: &'a [String],
-}
-
-impl<'a> Builder<'a> {
-    fn new(buildflags: &'a [String], profile: &'a str) -> Self {
-        Builder {
-            buildflags,
-            profile,
+        if args.get(1) == Some(&"watch".to_owned()) {
+            #[cfg(target_os = "linux")]
+            builder.watch(&metadata, &prefix, &launch, &appargs);
+            #[cfg(not(target_os = "linux"))]
+            {
+                println!("watch is only supported on Linux");
+                std::process::exit(1);
+            }
         }
-    }
-
-    fn build(&self, metadata: &Metadata, prefix: &PathBuf) -> bool {
-        let datadir = PathBuf::from("data");
-        let podir = PathBuf::from("po");
 
-        let outdir = metadata.targetdir.join(self.profile).join("data");
-        std::fs::create_dir_all(&outdir).expect(&format!(
-            "Error creating target/{}/data directory",
-            self.profile
-        ));
+        let issuccess = builder.build(&metadata, &prefix);
+        if !issuccess {
+            std::process::exit(1);
+        }
 
-        let commands = Commands {
-            datadir: &datadir,
-            podir: &podir,
-            metadata: &metadata,
-            profile: &self.profile,
+        if args.get(1) == Some(&"run".to_owned()) {
+            if let Some(lang) = &launch.lang {
+                let languages = runtime::languages(&metadata, &prefix);
+                if !languages.contains(lang) {
+                    let installed = if languages.is_empty() {
+                        "none".to_string()
+                    } else {
+                        languages.join(", ")
+                    };
+                    println!(
+                        "\x1b[1;33mwarning\x1b[0m: no translations for \"{}\" are installed, installed are: {}",
+                        lang, installed
+                    );
+                }
+            }
+            let vars = runtime::variables(&metadata, profile, &prefix, launch.memory_settings);
+            let mut command =
+                launch.command(&prefix.join("bin").join(&metadata.bin), &appargs, &vars);
+            let status = match command.status() {
+                Ok(status) => status,
+                Err(_) => {
+                    println!("{} not found!", command.get_program().to_string_lossy());
+                    std::process::exit(-1);
+                }
+            };
+            std::process::exit(status.code().unwrap_or(1));
+        }
+    } else if (args.get(1) == Some(&"env".to_owned()) || args.get(1) == Some(&"shell".to_owned()))
+        && args.len() > 2
+    {
+        let flags = &args[2..args.len() - 1];
+        let memory_settings = flags.contains(&"--memory-settings".to_owned());
+        let profile = if flags.contains(&"--debug".to_owned()) {
+            "debug"
+        } else {
+            "release"
         };
+        let format = flags
+            .iter()
+            .position(|flag| flag == "--format")
+            .and_then(|i| flags.get(i + 1))
+            .map(String::as_str)
+            .unwrap_or("sh");
+        let prefix = PathBuf::from(args.last().unwrap());
 
-        println!("\x1b[1;38;5;29m  Processing\x1b[0m .in files");
-        commands
-            .process_config_files(&outdir)
-            .expect("Error processing .in files");
-
-        println!("\x1b[1;38;5;29m   Compiling\x1b[0m langauge files");
-        commands
-            .install_langauge_files(&prefix)
-            .expect("Error compiling langage files");
-
-        //---------------------------------------------------------------
-        println!("\x1b[1;38;5;29m  Generating\x1b[0m appdata and desktop files");
-        let appdata = outdir.join(metadata.id.clone() + ".appdata.xml");
-        let desktop = outdir.join(metadata.id.clone() + ".desktop");
-        commands
-            .install_appdata_and_desktop(&appdata, &desktop, prefix)
-            .expect("Error installing appdata and desktop");
-        //-----------------------------------------------------------
-
-        //-----------------------------------------------------------
-        println!("\x1b[1;38;5;29m  Installing\x1b[0m glib resources");
-        let glibresource = outdir.join(metadata.id.clone() + ".gresource.xml");
-        commands
-            .install_glib_resources(&glibresource, prefix)
-            .expect("Error compiling and installing glib resources");
-        //-----------------------------------------------------------
-
-        //-----------------------------------------------------------
-        println!("\x1b[1;38;5;29m  Installing\x1b[0m icon files");
-        commands
-            .install_icon_files(prefix)
-            .expect("Error installing icons");
-        //-----------------------------------------------------------
-
-        //-----------------------------------------------------------
-        println!("\x1b[1;38;5;29m  Installing\x1b[0m glib schemas");
-        let gschema = datadir.join(metadata.id.clone() + ".gschema.xml");
-        commands
-            .install_glib_schemas(&gschema, prefix)
-            .expect("Error installing glib schemas");
-        //-----------------------------------------------------------
-
-        //-----------------------------------------------------------
-        println!("\x1b[1;38;5;29m  Generating\x1b[0m config.rs file");
-        commands
-            .generate_config_rs(&outdir, prefix)
-            .expect("Error generating config.rs");
-        //-----------------------------------------------------------
+        let metadata = Metadata::from("Cargo.toml").expect("Error parsing Cargo.toml");
+        let vars = runtime::variables(&metadata, profile, &prefix, memory_settings);
 
-        if let Some(out) = commands.install_binary(self.buildflags, prefix) {
-            if out.code() == Some(101) {
-                return false;
-            } else {
-                return true;
+        if args.get(1) == Some(&"shell".to_owned()) {
+            let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+            let status = Command::new(shell)
+                .envs(vars)
+                .status()
+                .expect("Error spawning shell");
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        match runtime::format(&vars, format) {
+            Some(out) => print!("{}", out),
+            None => {
+                println!("Unknown format \"{}\", expected sh, fish or json", format);
+                help();
             }
-        } else {
-            return false;
         }
-    }
-
-    pub fn create_project(id: &str, name: &str, bin: &str) -> Option<()> {
-        Command::new("cargo")
-            .args(&["new", "--bin", &bin])
-            .status()
-            .ok()?;
-
-        let toml = PathBuf::from(bin).join("Cargo.toml");
-
-        let template = std::fs::read_to_string(&toml).ok()?;
-        std::fs::write(
-            &toml,
-            &format!(
-                "{}[dependencies]
-log = \"0.4\"
-gettext-rs = {{ version = \"0.5\", features = [\"gettext-system\"] }}
-
-[dependencies.gtk]
-git = \"https://github.com/gtk-rs/gtk4\"
-package = \"gtk4\"
-
-[dependencies.glib]
-git = \"https://github.com/gtk-rs/glib\"
-features = [\"v2_60\"]
-
-[dependencies.gio]
-git = \"https://github.com/gtk-rs/gio\"
-features = [\"v2_60\"]
-
-[dependencies.gdk]
-git = \"https://github.com/gtk-rs/gdk4\"
-package = \"gdk4\"
-                \n\n[package.metadata.pkg]\nid = \"{}\"\nname = \"{}\"",
-                &template[..template.len() - 15],
-                id,
-                name
-            ),
-        )
-        .ok()?;
-
-        std::fs::write(
-            &PathBuf::from(bin).join("src").join("main.rs"),
-            "use gettextrs::*;
-            extern crate gio;
-extern crate gtk;
-
-use gio::prelude::*;
-use gtk::prelude::*;
-use gtk::ApplicationWindow;
-
-include!(env!(\"CONFIG_PATH\"));
-
-use std::env::args;
-
-fn main() {
-    // Initiialize gtk, gstreamer, and libhandy.
-    gtk::init().expect(\"Failed to initialize gtk!\");
-
-    // Setup language / translations
-    setlocale(LocaleCategory::LcAll, \"\");
-    bindtextdomain(GETTEXT_PACKAGE, LOCALEDIR);
-    textdomain(GETTEXT_PACKAGE);
-
-    // Register resources so we can integrate things like UI files, CSS, and icons
-    let res = gio::Resource::load(PKGDATADIR.to_owned() + \"/\" + APP_ID + \".gresource\")
-        .expect(\"Could not load resources\");
-    gio::resources_register(&res);
-
-    // Set up CSS
-    let provider = gtk::CssProvider::new();
-    provider.load_from_resource(&(GRESOURCE_ID.to_owned() + \"style.css\"));
-    gtk::StyleContext::add_provider_for_display(
-        &gdk::Display::get_default().unwrap(),
-        &provider,
-        600,
-    );
-
-    let application =
-        gtk::Application::new(Some(APP_ID), Default::default())
-            .expect(\"Initialization failed...\");
-
-    application.connect_activate(|app| {
-        let builder = gtk::Builder::from_resource(&(GRESOURCE_ID.to_owned() + \"window.ui\"));
-
-        let window: ApplicationWindow = builder.get_object(\"window\").expect(\"Couldn't get window\");
-        window.set_application(Some(app));
-    
-        window.show();
-    });
-
-    application.run(&args().collect::<Vec<_>>());
-}",
-        )
-        .ok()?;
-
-        let datadir = PathBuf::from(bin.to_string() + "/data");
-        std::fs::create_dir_all(&datadir.join("resources")).ok()?;
-        std::fs::create_dir_all(&datadir.join("icons")).ok()?;
-
-        let podir = PathBuf::from(bin.to_string() + "/po");
-        std::fs::create_dir_all(&podir).ok()?;
-
-        std::fs::write(
-            datadir.join(id.to_owned() + ".gschema.xml.in"),
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
-<schemalist gettext-domain=\"@GETTEXT_DOMAIN@\">
-    <schema id=\"@APP_ID@\" path=\"/@GRESOURCE_ID@/\">
-    </schema>
-</schemalist>",
-        )
-        .ok()?;
-
-        std::fs::write(
-            datadir.join(id.to_owned() + ".desktop.in"),
-            "[Desktop Entry]
-Name=@APP_NAME@
-Exec=@APP_BINARY@
-Icon=@APP_ID@ // Do not translate
-Terminal=false
-Type=Application
-StartupNotify=true",
-        )
-        .ok()?;
-
-        std::fs::write(
-            datadir.join(id.to_owned() + ".appdata.xml.in"),
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
-<component>
-    <id>@APP_ID@</id>
-    <name>@APP_NAME@</name>
-    <summary>A foo-ish bar</summary>
-    <url type=\"homepage\">http://www.example.org</url>
-    <metadata_license>CC0-1.0</metadata_license>
-    <provides>
-        <binary>@APP_BINARY@</binary>
-    </provides>
-    <releases>
-        <release version=\"@APP_VERSION@\"/>
-    </releases>
-    <developer_name>FooBar Team</developer_name>
-</component>",
-        )
-        .ok()?;
-
-        std::fs::write(
-            datadir.join(id.to_owned() + ".gresource.xml.in"),
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
-<gresources>
-    <gresource prefix=\"/@GRESOURCE_ID@/\">
-        <file compressed=\"true\" preprocess=\"xml-stripblanks\">window.ui</file>
-        <file compressed=\"true\" alias=\"style.css\">style.css</file>
-    </gresource>
-</gresources>",
-        )
-        .ok()?;
-
-        std::fs::write(
-            datadir.join("resources").join("window.ui"),
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
-<interface>
-    <object class=\"GtkApplicationWindow\" id=\"window\">
-    <property name=\"visible\">True</property>
-    <property name=\"can_focus\">False</property>
-    <property name=\"default_width\">600</property>
-    <property name=\"default_height\">400</property>
-    <child type=\"titlebar\">
-        <object class=\"GtkHeaderBar\" id=\"headerbar\">
-        <property name=\"visible\">True</property>
-        <property name=\"can_focus\">False</property>
-        </object>
-    </child>
-    <child>
-        <object class=\"GtkLabel\" id=\"label\">
-        <property name=\"visible\">True</property>
-        <property name=\"can_focus\">False</property>
-        <property name=\"label\" translatable=\"yes\">Hello world!</property>
-        <style>
-            <class name=\"title-header\"/>
-        </style>
-        </object>
-    </child>
-    </object>
-</interface>",
-        )
-        .ok()?;
-
-        std::fs::write(
-            datadir.join("resources").join("style.css"),
-            ".title-header { font-size: 40px }",
-        )
-        .ok()?;
-
-        std::fs::write(podir.join("LINGUAS"), "").ok()?;
-        std::fs::write(podir.join("POTFILES.in"), "src/main.rs").ok()?;
+    } else if args.get(1) == Some(&"pot".to_owned()) {
+        let metadata = Metadata::from("Cargo.toml").expect("Error parsing Cargo.toml");
+        let builder = Builder::new(&[], "release", false);
+
+        println!(
+            "\x1b[1;38;5;29m{:>12}\x1b[0m translatable strings",
+            "Extracting"
+        );
+        match builder.pot(&metadata) {
+            Some(pot) => {
+                // Every msgid but the header's
+                let strings = std::fs::read_to_string(&pot)
+                    .map(|text| text.lines().filter(|l| l.starts_with("msgid ")).count())
+                    .unwrap_or(1);
+                println!(
+                    "\x1b[1;38;5;29m{:>12}\x1b[0m {} ({} strings)",
+                    "Wrote",
+                    pot.display(),
+                    strings.saturating_sub(1)
+                );
+            }
+            None => {
+                println!("\x1b[1;31merror\x1b[0m: Error extracting translatable strings");
+                std::process::exit(1);
+            }
+        }
+    } else if args.get(1) == Some(&"update-po".to_owned()) {
+        let metadata = Metadata::from("Cargo.toml").expect("Error parsing Cargo.toml");
+        let builder = Builder::new(&[], "release", false);
+        if builder.update_po(&metadata).is_none() {
+            println!("\x1b[1;31merror\x1b[0m: Error updating translations");
+            std::process::exit(1);
+        }
+    } else if args.get(1) == Some(&"i18n-stats".to_owned()) {
+        let flags = &args[2..];
+        let format = flags
+            .iter()
+            .position(|flag| flag == "--format")
+            .and_then(|i| flags.get(i + 1))
+            .map(String::as_str)
+            .unwrap_or("text");
+        let min_percent = match flags.iter().position(|flag| flag == "--min-percent") {
+            Some(i) => percent(flags.get(i + 1)),
+            None => 0,
+        };
 
-        Some(())
+        let metadata = Metadata::from("Cargo.toml").expect("Error parsing Cargo.toml");
+        let mut builder = Builder::new(&[], "release", false);
+        builder.min_percent = min_percent;
+        match builder.i18n_stats(&metadata, format) {
+            Some(out) => print!("{}", out),
+            None => {
+                println!("Unknown format \"{}\", expected text or json", format);
+                help();
+            }
+        }
+    } else {
+        println!("Invalid arguments");
+        help();
     }
 }
@@ -1,7 +1,56 @@
 use std::fs::read_to_string;
-use std::path::PathBuf;
-use std::process::Command;
-use toml::{from_str, value::Value};
+use std::path::{Path, PathBuf};
+use toml::value::Value;
+
+// A command from [package.metadata.pkg.hooks]. Strings named
+// pre-<step> or post-<step> run before or after that step,
+// tables can also set before, after, inputs and outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub name: String,
+    pub command: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    // Relative to the crate, $PREFIX and $OUTDIR are expanded
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+impl Hook {
+    fn from(name: &str, value: &Value) -> Option<Hook> {
+        let strings = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_array)
+                .map(|array| {
+                    array
+                        .iter()
+                        .filter_map(|value| Some(value.as_str()?.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let position = |key: &str, prefix: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .or_else(|| name.strip_prefix(prefix))
+                .map(str::to_string)
+        };
+
+        Some(Hook {
+            name: name.to_string(),
+            command: value
+                .as_str()
+                .or_else(|| value.get("command")?.as_str())?
+                .to_string(),
+            before: position("before", "pre-"),
+            after: position("after", "post-"),
+            inputs: strings("inputs"),
+            outputs: strings("outputs"),
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Metadata {
@@ -10,12 +59,24 @@ pub struct Metadata {
     pub name: String,
     pub version: String,
     pub targetdir: PathBuf,
+    // Build the gresource bundle into the binary
+    // instead of installing it to share/{app_id}
+    pub embed_resources: bool,
+    // Compile gschemas.compiled without glib-compile-schemas
+    pub native_schemas: bool,
+    // Name of the .mo files and GETTEXT_PACKAGE, the binary's by default
+    pub gettext_domain: String,
+    // Compile .mo files with msgfmt instead of natively
+    pub use_msgfmt: bool,
+    pub hooks: Vec<Hook>,
+    // Local crates the binary is built from, e.g. core = { path = "../core" }
+    pub path_dependencies: Vec<PathBuf>,
 }
 
 impl Metadata {
     pub fn from(path: &str) -> Option<Metadata> {
-        let toml_str = read_to_string("Cargo.toml").expect("Error reading Cargo.toml!");
-        let meta: Value = toml::from_str(&toml_str).expect("Error parsing Cargo.toml!");
+        let toml_str = read_to_string(path).ok()?;
+        let meta: Value = toml::from_str(&toml_str).ok()?;
         // println!("{:#?}", meta);
 
         let package = &meta.get("package")?;
@@ -26,6 +87,40 @@ impl Metadata {
 
         let id = metadata.get("pkg")?.get("id")?.as_str()?.to_string();
         let name = metadata.get("pkg")?.get("name")?.as_str()?.to_string();
+        let embed_resources = metadata
+            .get("pkg")?
+            .get("embed-resources")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let native_schemas = metadata
+            .get("pkg")?
+            .get("native-schemas")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let use_msgfmt = metadata
+            .get("pkg")?
+            .get("use-msgfmt")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let gettext_domain = metadata
+            .get("pkg")?
+            .get("gettext-domain")
+            .and_then(Value::as_str)
+            .unwrap_or(&bin)
+            .to_string();
+
+        let mut hooks = Vec::new();
+        if let Some(table) = metadata.get("pkg")?.get("hooks").and_then(Value::as_table) {
+            for (name, value) in table {
+                match Hook::from(name, value) {
+                    Some(hook) => hooks.push(hook),
+                    None => println!(
+                        "\x1b[1;33mwarning\x1b[0m: hook \"{}\" has no command, ignoring it",
+                        name
+                    ),
+                }
+            }
+        }
 
         const DEFAULT_TARGET_DIR: &str = "./target";
 
@@ -37,12 +132,30 @@ impl Metadata {
             .as_str()?
             .to_string();
 
+        // Relative to the crate, not to where cargo pkg runs
+        let cratedir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let targetdir = cratedir.join(targetdir);
+
+        let path_dependencies = ["dependencies", "build-dependencies"]
+            .iter()
+            .filter_map(|key| meta.get(key)?.as_table())
+            .flat_map(|table| table.values())
+            .filter_map(|dependency| dependency.get("path")?.as_str())
+            .map(|path| cratedir.join(path))
+            .collect();
+
         Some(Self {
             id,
             bin,
             name,
             version,
-            targetdir: PathBuf::from(targetdir),
+            targetdir,
+            embed_resources,
+            native_schemas,
+            gettext_domain,
+            use_msgfmt,
+            hooks,
+            path_dependencies,
         })
     }
 }
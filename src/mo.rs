@@ -0,0 +1,335 @@
+use super::po::{Catalog, Message};
+use std::path::Path;
+
+// Compile po into mo, the binary catalog gettext loads at runtime.
+// Untranslated, fuzzy and obsolete messages are left out.
+pub fn compile(po: &Path, mo: &Path) -> Option<()> {
+    let catalog = Catalog::from(po)?;
+
+    let errors = check(&catalog);
+    for (line, err) in &errors {
+        println!("\x1b[1;31merror\x1b[0m: {}:{}: {}", po.display(), line, err);
+    }
+    if !errors.is_empty() {
+        return None;
+    }
+
+    std::fs::write(mo, write(&catalog)).ok()
+}
+
+// Problems msgfmt --check would report, which
+// would show up as wrong or crashing strings at runtime.
+fn check(catalog: &Catalog) -> Vec<(usize, String)> {
+    let mut errors = Vec::new();
+    let nplurals = catalog
+        .header_field("Plural-Forms")
+        .and_then(|forms| {
+            forms
+                .split(';')
+                .find_map(|part| part.trim().strip_prefix("nplurals="))
+        })
+        .and_then(|n| n.trim().parse::<usize>().ok());
+
+    for message in catalog.current().filter(|message| message.is_translated()) {
+        if message.plural.is_some() {
+            match nplurals {
+                Some(n) if message.strings.len() != n => errors.push((
+                    message.line,
+                    format!(
+                        "expected {} plural forms, found {}",
+                        n,
+                        message.strings.len()
+                    ),
+                )),
+                None => errors.push((
+                    message.line,
+                    "plural message without a Plural-Forms header".to_string(),
+                )),
+                _ => {}
+            }
+        }
+        if let Err(err) = check_format(message) {
+            errors.push((message.line, err));
+        }
+    }
+    errors
+}
+
+// Translations must use the same {} placeholders as the source, otherwise
+// formatting them panics or drops arguments. Plural forms may leave some
+// out, "one file" doesn't need the count.
+fn check_format(message: &Message) -> Result<(), String> {
+    if message.flags.iter().any(|flag| flag == "no-rust-format") {
+        return Ok(());
+    }
+    let id = placeholders(&message.id).unwrap_or_default();
+    let plural = message.plural.as_deref().and_then(placeholders);
+    let forced = message.flags.iter().any(|flag| flag == "rust-format");
+    if id.is_empty() && plural.as_ref().is_none_or(Vec::is_empty) && !forced {
+        return Ok(());
+    }
+
+    for (index, string) in message.strings.iter().enumerate() {
+        let source = match &plural {
+            Some(plural) if index > 0 => plural,
+            _ => &id,
+        };
+        let translated =
+            placeholders(string).ok_or_else(|| format!("invalid format string \"{}\"", string))?;
+
+        let count = |list: &[String]| list.iter().filter(|arg| arg.is_empty()).count();
+        let (expected, found) = (count(source), count(&translated));
+        if found > expected || (message.plural.is_none() && found < expected) {
+            return Err(format!(
+                "expected {} `{{}}` placeholders, found {} in \"{}\"",
+                expected, found, string
+            ));
+        }
+
+        for arg in translated.iter().filter(|arg| !arg.is_empty()) {
+            if !source.contains(arg) {
+                return Err(format!(
+                    "unknown placeholder `{{{}}}` in \"{}\"",
+                    arg, string
+                ));
+            }
+        }
+        if message.plural.is_none() {
+            for arg in source.iter().filter(|arg| !arg.is_empty()) {
+                if !translated.contains(arg) {
+                    return Err(format!(
+                        "missing placeholder `{{{}}}` in \"{}\"",
+                        arg, string
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Arguments of the placeholders in a format string, "" for {} and {:?},
+// "name" for {name:>5}. None if braces don't match up.
+fn placeholders(text: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        '{' => return None,
+                        c => inner.push(c),
+                    }
+                }
+                let arg = inner.split(':').next().unwrap_or("").trim();
+                args.push(arg.to_string());
+            }
+            '}' => return None,
+            _ => {}
+        }
+    }
+    Some(args)
+}
+
+// The GNU mo format: a header, two tables of (length, offset)
+// pairs pointing at the NUL terminated originals and translations,
+// sorted by original, and no hash table.
+pub fn write(catalog: &Catalog) -> Vec<u8> {
+    let mut entries = catalog
+        .messages
+        .iter()
+        .filter(|message| !message.obsolete)
+        .filter(|message| message.is_header() || message.is_translated())
+        .map(|message| {
+            // Contexts are joined to the id with EOT, plurals with NUL
+            let mut original = String::new();
+            if let Some(context) = &message.context {
+                original.push_str(context);
+                original.push('\x04');
+            }
+            original.push_str(&message.id);
+            if let Some(plural) = &message.plural {
+                original.push('\0');
+                original.push_str(plural);
+            }
+            (
+                original.into_bytes(),
+                message.strings.join("\0").into_bytes(),
+            )
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+    entries.dedup_by(|a, b| a.0 == b.0);
+
+    let count = entries.len() as u32;
+    let originals = 28;
+    let translations = originals + 8 * count;
+    let mut offset = translations + 8 * count;
+
+    let mut out = Vec::new();
+    for value in &[0x9504_12de, 0, count, originals, translations, 0, offset] {
+        out.extend_from_slice(&u32::to_le_bytes(*value));
+    }
+
+    let mut strings = Vec::new();
+    let mut table = Vec::new();
+    for string in entries
+        .iter()
+        .map(|(original, _)| original)
+        .chain(entries.iter().map(|(_, translation)| translation))
+    {
+        table.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        table.extend_from_slice(&offset.to_le_bytes());
+        strings.extend_from_slice(string);
+        strings.push(0);
+        offset += string.len() as u32 + 1;
+    }
+    out.extend(table);
+    out.extend(strings);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "msgid \"\"\nmsgstr \"Plural-Forms: nplurals=2; plural=(n != 1);\\n\"\n\n";
+
+    fn errors(po: &str) -> Vec<String> {
+        let catalog = Catalog::parse(&format!("{}{}", HEADER, po)).unwrap();
+        check(&catalog).into_iter().map(|(_, err)| err).collect()
+    }
+
+    fn format(id: &str, string: &str) -> Result<(), String> {
+        check_format(&Message {
+            id: id.to_string(),
+            strings: vec![string.to_string()],
+            ..Message::default()
+        })
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    // The string a (length, offset) pair of the table at table points to
+    fn string_at(data: &[u8], table: u32, index: usize) -> &[u8] {
+        let at = table as usize + 8 * index;
+        let (len, offset) = (u32_at(data, at) as usize, u32_at(data, at + 4) as usize);
+        assert_eq!(data[offset + len], 0);
+        &data[offset..offset + len]
+    }
+
+    #[test]
+    fn plurals() {
+        let po = "msgid \"{} file\"\nmsgid_plural \"{} files\"\nmsgstr[0] \"{} Datei\"\n";
+        assert_eq!(errors(po), ["expected 2 plural forms, found 1"]);
+        let po = format!("{}msgstr[1] \"{{}} Dateien\"\n", po);
+        assert!(errors(&po).is_empty());
+        let catalog = Catalog::parse(&po).unwrap();
+        assert_eq!(
+            check(&catalog),
+            [(
+                1,
+                "plural message without a Plural-Forms header".to_string()
+            )]
+        );
+        // "one file" may leave out the count, but not add one
+        let po = "msgid \"{} file\"\nmsgid_plural \"{} files\"\nmsgstr[0] \"eine Datei\"\nmsgstr[1] \"{} {} Dateien\"\n";
+        assert_eq!(
+            errors(po),
+            ["expected 1 `{}` placeholders, found 2 in \"{} {} Dateien\""]
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert!(format("{} of {}", "{} von {}").is_ok());
+        assert!(format("{name} is {age:>3}", "{age} Jahre: {name}").is_ok());
+        assert!(format("100%", "100 %s").is_ok());
+        assert!(format("{{literal}}", "{{wörtlich}}").is_ok());
+        assert_eq!(
+            format("{} of {}", "{} von").unwrap_err(),
+            "expected 2 `{}` placeholders, found 1 in \"{} von\""
+        );
+        assert_eq!(
+            format("Hello {name}", "Hallo {nmae}").unwrap_err(),
+            "unknown placeholder `{nmae}` in \"Hallo {nmae}\""
+        );
+        assert_eq!(
+            format("{name} and {other}", "{name}").unwrap_err(),
+            "missing placeholder `{other}` in \"{name}\""
+        );
+        assert_eq!(
+            format("Hello {}", "Hallo {").unwrap_err(),
+            "invalid format string \"Hallo {\""
+        );
+        // Plain text with braces only counts when flagged
+        let flagged = |flag: &str| {
+            check_format(&Message {
+                id: "no placeholders".to_string(),
+                strings: vec!["{oops".to_string()],
+                flags: vec![flag.to_string()],
+                ..Message::default()
+            })
+        };
+        assert!(flagged("c-format").is_ok());
+        assert!(flagged("rust-format").is_err());
+        assert!(format("{}", "%s").is_err());
+        assert!(check_format(&Message {
+            id: "{}".to_string(),
+            strings: vec!["%s".to_string()],
+            flags: vec!["no-rust-format".to_string()],
+            ..Message::default()
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn layout() {
+        let po = format!(
+            "{}{}",
+            HEADER,
+            "msgid \"b\"\nmsgstr \"B\"\n\n\
+             msgctxt \"ctx\"\nmsgid \"a\"\nmsgstr \"A\"\n\n\
+             msgid \"c\"\nmsgid_plural \"cs\"\nmsgstr[0] \"C\"\nmsgstr[1] \"Cs\"\n\n\
+             #, fuzzy\nmsgid \"fuzzy\"\nmsgstr \"F\"\n\n\
+             msgid \"untranslated\"\nmsgstr \"\"\n\n\
+             #~ msgid \"obsolete\"\n#~ msgstr \"O\"\n"
+        );
+        let data = write(&Catalog::parse(&po).unwrap());
+
+        // magic, revision, count, originals, translations, hash size and offset
+        let header = (0..7).map(|i| u32_at(&data, 4 * i)).collect::<Vec<_>>();
+        assert_eq!(header, [0x9504_12de, 0, 4, 28, 60, 0, 92]);
+
+        let originals = (0..4).map(|i| string_at(&data, 28, i)).collect::<Vec<_>>();
+        let translations = (0..4).map(|i| string_at(&data, 60, i)).collect::<Vec<_>>();
+        assert_eq!(originals, [&b""[..], b"b", b"c\0cs", b"ctx\x04a"]);
+        assert_eq!(
+            translations,
+            [
+                &b"Plural-Forms: nplurals=2; plural=(n != 1);\n"[..],
+                b"B",
+                b"C\0Cs",
+                b"A"
+            ]
+        );
+        // Strings follow the tables back to back
+        assert_eq!(u32_at(&data, 28 + 4), 92);
+        let last = string_at(&data, 60, 3);
+        assert_eq!(
+            data.len(),
+            u32_at(&data, 60 + 8 * 3 + 4) as usize + last.len() + 1
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::path::Path;
+
+// An entry of a .po or .pot file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub context: Option<String>,
+    pub id: String,
+    pub plural: Option<String>,
+    // msgstr, or msgstr[n] of a plural message
+    pub strings: Vec<String>,
+    // From #, lines, e.g. fuzzy or c-format
+    pub flags: Vec<String>,
+    // Kept as #~ once it's gone from the template
+    pub obsolete: bool,
+    // Where the message starts, for errors
+    pub line: usize,
+}
+
+impl Message {
+    pub fn is_header(&self) -> bool {
+        self.id.is_empty() && self.context.is_none()
+    }
+
+    pub fn is_fuzzy(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "fuzzy")
+    }
+
+    // Fuzzy and partly translated messages don't count
+    pub fn is_translated(&self) -> bool {
+        !self.is_fuzzy()
+            && !self.strings.is_empty()
+            && self.strings.iter().all(|string| !string.is_empty())
+    }
+
+    // Messages are told apart by context and id
+    pub fn key(&self) -> (Option<&str>, &str) {
+        (self.context.as_deref(), &self.id)
+    }
+}
+
+// How much of a catalog is translated
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub translated: usize,
+    pub fuzzy: usize,
+    pub untranslated: usize,
+}
+
+impl Stats {
+    pub fn total(&self) -> usize {
+        self.translated + self.fuzzy + self.untranslated
+    }
+
+    // Nothing to translate counts as done
+    pub fn percent(&self) -> f64 {
+        if self.total() == 0 {
+            100.0
+        } else {
+            self.translated as f64 * 100.0 / self.total() as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub messages: Vec<Message>,
+}
+
+impl Catalog {
+    pub fn from(path: &Path) -> Option<Catalog> {
+        let text = match std::fs::read(path).map(String::from_utf8) {
+            Ok(Ok(text)) => text,
+            Ok(Err(err)) => {
+                println!(
+                    "\x1b[1;31merror\x1b[0m: {}: isn't valid UTF-8 at byte {}, convert it with msgconv --to-code=UTF-8",
+                    path.display(),
+                    err.utf8_error().valid_up_to()
+                );
+                return None;
+            }
+            Err(err) => {
+                println!("\x1b[1;31merror\x1b[0m: {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        match Catalog::parse(&text) {
+            Ok(catalog) => Some(catalog),
+            Err((line, err)) => {
+                println!(
+                    "\x1b[1;31merror\x1b[0m: {}:{}: {}",
+                    path.display(),
+                    line,
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Catalog, (usize, String)> {
+        let mut messages = Vec::new();
+        let mut message = Message::default();
+        // Which field continuation lines belong to
+        let mut field: Option<(&str, usize)> = None;
+        let mut started = false;
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let mut line = line.trim();
+            let obsolete = line.starts_with("#~");
+            if obsolete {
+                line = line[2..].trim_start();
+                // #~| is the previous msgid of an obsolete message
+                if line.starts_with('|') {
+                    continue;
+                }
+            } else if let Some(flags) = line.strip_prefix("#,") {
+                if started {
+                    messages.push(std::mem::take(&mut message));
+                    field = None;
+                    started = false;
+                }
+                message
+                    .flags
+                    .extend(flags.split(',').map(|flag| flag.trim().to_string()));
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let keyword = line
+                .split(|c: char| c.is_whitespace() || c == '"')
+                .next()
+                .unwrap_or("");
+            let rest = line[keyword.len()..].trim();
+            let value = if keyword.is_empty() {
+                rest
+            } else {
+                // A new message starts with msgctxt or msgid
+                if (keyword == "msgctxt" || (keyword == "msgid" && field != Some(("msgctxt", 0))))
+                    && started
+                {
+                    messages.push(std::mem::take(&mut message));
+                    started = false;
+                }
+                field = match keyword {
+                    "msgctxt" => Some(("msgctxt", 0)),
+                    "msgid" => Some(("msgid", 0)),
+                    "msgid_plural" => Some(("msgid_plural", 0)),
+                    "msgstr" => Some(("msgstr", 0)),
+                    _ => match keyword
+                        .strip_prefix("msgstr[")
+                        .and_then(|index| index.strip_suffix(']'))
+                        .and_then(|index| index.parse().ok())
+                    {
+                        Some(index) => Some(("msgstr", index)),
+                        None => return Err((number, format!("unknown keyword {}", keyword))),
+                    },
+                };
+                if !started {
+                    message.line = number;
+                }
+                started = true;
+                message.obsolete = obsolete;
+                rest
+            };
+
+            let string = unescape(value).ok_or((number, "invalid string".to_string()))?;
+            match field {
+                Some(("msgctxt", _)) => message
+                    .context
+                    .get_or_insert_with(String::new)
+                    .push_str(&string),
+                Some(("msgid", _)) => message.id.push_str(&string),
+                Some(("msgid_plural", _)) => message
+                    .plural
+                    .get_or_insert_with(String::new)
+                    .push_str(&string),
+                Some((_, index)) => {
+                    if message.strings.len() <= index {
+                        message.strings.resize(index + 1, String::new());
+                    }
+                    message.strings[index].push_str(&string);
+                }
+                None => return Err((number, "string outside of a message".to_string())),
+            }
+        }
+        if started {
+            messages.push(message);
+        }
+
+        // Strings are read as UTF-8, others would come out garbled.
+        // Templates say CHARSET until a translator picks one.
+        let catalog = Catalog { messages };
+        if let Some(charset) = catalog.charset() {
+            if !["utf-8", "utf8", "ascii", "us-ascii", "charset"]
+                .contains(&charset.to_lowercase().as_str())
+            {
+                return Err((
+                    catalog.header().map_or(1, |header| header.line),
+                    format!(
+                        "charset {} isn't supported, convert the file with msgconv --to-code=UTF-8",
+                        charset
+                    ),
+                ));
+            }
+        }
+        Ok(catalog)
+    }
+
+    // Messages besides the header which aren't obsolete
+    pub fn current(&self) -> impl Iterator<Item = &Message> {
+        self.messages
+            .iter()
+            .filter(|message| !message.is_header() && !message.obsolete)
+    }
+
+    // The msgstr of the "" entry
+    pub fn header(&self) -> Option<&Message> {
+        self.messages
+            .iter()
+            .find(|message| message.is_header() && !message.obsolete)
+    }
+
+    // A "Name: value" line of the header, e.g. Plural-Forms
+    pub fn header_field(&self, name: &str) -> Option<&str> {
+        self.header()?
+            .strings
+            .first()?
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(field, _)| field.trim() == name)
+            .map(|(_, value)| value.trim())
+    }
+
+    // charset= of the Content-Type header field
+    fn charset(&self) -> Option<&str> {
+        self.header_field("Content-Type")?
+            .split(';')
+            .find_map(|part| part.trim().strip_prefix("charset="))
+            .map(str::trim)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for message in self.current() {
+            if message.is_translated() {
+                stats.translated += 1;
+            } else if message.is_fuzzy() {
+                stats.fuzzy += 1;
+            } else {
+                stats.untranslated += 1;
+            }
+        }
+        stats
+    }
+
+    // msgstr of a translated message without plural
+    pub fn translate(&self, context: Option<&str>, id: &str) -> Option<&str> {
+        let message = self.find(context, id)?;
+        if message.is_translated() && message.plural.is_none() {
+            message.strings.first().map(String::as_str)
+        } else {
+            None
+        }
+    }
+
+    pub fn find(&self, context: Option<&str>, id: &str) -> Option<&Message> {
+        self.current()
+            .find(|message| message.key() == (context, id))
+    }
+}
+
+// "a\tb" to a<tab>b
+fn unescape(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'a' => out.push('\x07'),
+            'b' => out.push('\x08'),
+            'f' => out.push('\x0c'),
+            'v' => out.push('\x0b'),
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PO: &str = r#"# German translation
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+#: src/main.rs:10
+msgid ""
+"A long line "
+"continued"
+msgstr "Eine lange Zeile "
+"fortgesetzt"
+
+msgid "Tab\there \"quoted\"\n"
+msgstr "Tab\there \"zitiert\"\n"
+
+msgctxt "menu"
+msgid "Open"
+msgstr "Öffnen"
+
+msgid "Open"
+msgstr "Aufmachen"
+
+#, fuzzy
+msgid "Close"
+msgstr "Schließen"
+
+msgid "Quit"
+msgstr ""
+
+msgid "{} file"
+msgid_plural "{} files"
+msgstr[0] "{} Datei"
+msgstr[1] "{} Dateien"
+
+#~ msgid "Gone"
+#~ msgstr "Weg"
+"#;
+
+    #[test]
+    fn parse() {
+        let catalog = Catalog::parse(PO).unwrap();
+        assert_eq!(
+            catalog.header_field("Plural-Forms"),
+            Some("nplurals=2; plural=(n != 1);")
+        );
+        assert_eq!(
+            catalog.translate(None, "A long line continued"),
+            Some("Eine lange Zeile fortgesetzt")
+        );
+        assert_eq!(
+            catalog.translate(None, "Tab\there \"quoted\"\n"),
+            Some("Tab\there \"zitiert\"\n")
+        );
+        assert_eq!(catalog.translate(Some("menu"), "Open"), Some("Öffnen"));
+        assert_eq!(catalog.translate(None, "Open"), Some("Aufmachen"));
+
+        let plural = catalog.find(None, "{} file").unwrap();
+        assert_eq!(plural.plural.as_deref(), Some("{} files"));
+        assert_eq!(plural.strings, ["{} Datei", "{} Dateien"]);
+        assert_eq!(plural.line, 31);
+    }
+
+    #[test]
+    fn skipped() {
+        let catalog = Catalog::parse(PO).unwrap();
+        assert!(catalog.find(None, "Close").unwrap().is_fuzzy());
+        assert_eq!(catalog.translate(None, "Close"), None);
+        assert_eq!(catalog.translate(None, "Quit"), None);
+        assert_eq!(catalog.find(None, "Gone"), None);
+        assert!(catalog
+            .messages
+            .iter()
+            .any(|message| message.obsolete && message.id == "Gone"));
+
+        let stats = catalog.stats();
+        assert_eq!(
+            (stats.translated, stats.fuzzy, stats.untranslated),
+            (5, 1, 1)
+        );
+        assert_eq!(stats.total(), 7);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Catalog::parse("msgid \"a\"\nmsgstr \"b\nmsgfoo \"c\"\n").unwrap_err(),
+            (2, "invalid string".to_string())
+        );
+        assert_eq!(
+            Catalog::parse("msgid \"a\"\nmsgfoo \"c\"\n").unwrap_err(),
+            (2, "unknown keyword msgfoo".to_string())
+        );
+        let latin1 = "\nmsgid \"\"\nmsgstr \"Content-Type: text/plain; charset=ISO-8859-1\\n\"\n";
+        let (line, err) = Catalog::parse(latin1).unwrap_err();
+        assert_eq!(line, 2);
+        assert!(err.starts_with("charset ISO-8859-1 isn't supported"));
+        assert!(Catalog::parse(&latin1.replace("ISO-8859-1", "CHARSET")).is_ok());
+    }
+}
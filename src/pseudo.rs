@@ -0,0 +1,164 @@
+use super::po::{Catalog, Message};
+
+// Installed next to the real languages, run with --lang en@pseudo
+pub const LOCALE: &str = "en@pseudo";
+
+// Every string of the template, accented, a third longer and
+// in brackets, so untranslated strings stand out in the UI as
+// plain text and cut off ones as missing a bracket.
+pub fn catalog(template: &Catalog) -> Catalog {
+    let header = Message {
+        strings: vec![[
+            "Content-Type: text/plain; charset=UTF-8\n",
+            "Language: ",
+            LOCALE,
+            "\n",
+            "Plural-Forms: nplurals=2; plural=(n != 1);\n",
+        ]
+        .concat()],
+        ..Message::default()
+    };
+
+    let mut messages = vec![header];
+    for message in template.current() {
+        let strings = match &message.plural {
+            Some(plural) => vec![pseudo(&message.id), pseudo(plural)],
+            None => vec![pseudo(&message.id)],
+        };
+        messages.push(Message {
+            context: message.context.clone(),
+            id: message.id.clone(),
+            plural: message.plural.clone(),
+            strings,
+            ..Message::default()
+        });
+    }
+    Catalog { messages }
+}
+
+// "Open {name}" becomes "[Öƥéñ {name}~~]", placeholders,
+// markup and mnemonics are kept as they are.
+fn pseudo(text: &str) -> String {
+    let mut out = String::from("[");
+    let mut letters: usize = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match kept(rest) {
+            0 => {
+                if c.is_alphabetic() {
+                    letters += 1;
+                }
+                out.push(accent(c));
+                c.len_utf8()
+            }
+            len => {
+                out.push_str(&rest[..len]);
+                len
+            }
+        };
+        rest = &rest[len..];
+    }
+    // Translations tend to be about a third longer than English
+    out.extend(std::iter::repeat_n('~', letters.div_ceil(3)));
+    out.push(']');
+    out
+}
+
+// Length of the {placeholder}, <tag>, &entity; or %d at the start of text
+fn kept(text: &str) -> usize {
+    let through = |close: char| text.find(close).map_or(0, |i| i + 1);
+    if text.starts_with("{{") || text.starts_with("}}") {
+        return 2;
+    }
+    match text.chars().next() {
+        Some('{') => through('}'),
+        Some('<') => through('>'),
+        Some('&') => match text.find(';') {
+            Some(i)
+                if i > 1
+                    && text[1..i]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '#') =>
+            {
+                i + 1
+            }
+            _ => 0,
+        },
+        Some('%') => {
+            match text[1..].find(|c: char| !(c.is_ascii_digit() || "$.-+#l".contains(c))) {
+                Some(i)
+                    if text[1 + i..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '%') =>
+                {
+                    i + 2
+                }
+                _ => 0,
+            }
+        }
+        _ => 0,
+    }
+}
+
+fn accent(c: char) -> char {
+    const PLAIN: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const ACCENTED: &str = "åƀçđéƒĝĥîĵķļɱñöƥǫŕšţûṽŵẋýžÅƁÇĐÉƑĜĤÎĴĶĻṀÑÖƤǪŔŠŢÛṼŴẊÝŽ";
+    match PLAIN.find(c) {
+        Some(index) => ACCENTED.chars().nth(index).unwrap_or(c),
+        None => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accents() {
+        assert_eq!(pseudo("Open {name}"), "[Öƥéñ {name}~~]");
+        assert_eq!(pseudo("Quit"), "[Ǫûîţ~~]");
+        assert_eq!(pseudo(""), "[]");
+        assert_eq!(pseudo("1, 2, 3…"), "[1, 2, 3…]");
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(pseudo("{} of {}"), "[{} öƒ {}~]");
+        assert_eq!(pseudo("{count:>3} {name}"), "[{count:>3} {name}]");
+        assert_eq!(pseudo("{{literal}}"), "[{{ļîţéŕåļ}}~~~]");
+        assert_eq!(pseudo("%d of %1$s, 100%%"), "[%d öƒ %1$s, 100%%~]");
+        assert_eq!(pseudo("50% off"), "[50% öƒƒ~]");
+    }
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            pseudo("<b>Bold</b> &amp; <a href=\"x\">link</a>"),
+            "[<b>Ɓöļđ</b> &amp; <a href=\"x\">ļîñķ</a>~~~]"
+        );
+        assert_eq!(pseudo("&#169; Tom & Jerry"), "[&#169; Ţöɱ & Ĵéŕŕý~~~]");
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(pseudo("_Open"), "[_Öƥéñ~~]");
+        assert_eq!(pseudo("Save _As…"), "[Šåṽé _Åš…~~]");
+    }
+
+    #[test]
+    fn messages() {
+        let template = Catalog::parse(
+            "msgctxt \"menu\"\nmsgid \"_Open\"\nmsgstr \"\"\n\n\
+             msgid \"{} file\"\nmsgid_plural \"{} files\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n\n\
+             #~ msgid \"Gone\"\n#~ msgstr \"\"\n",
+        )
+        .unwrap();
+        let catalog = catalog(&template);
+        assert_eq!(catalog.header_field("Language"), Some(LOCALE));
+        assert_eq!(catalog.translate(Some("menu"), "_Open"), Some("[_Öƥéñ~~]"));
+        assert_eq!(
+            catalog.find(None, "{} file").unwrap().strings,
+            ["[{} ƒîļé~~]", "[{} ƒîļéš~~]"]
+        );
+        assert_eq!(catalog.find(None, "Gone"), None);
+        assert_eq!(catalog.stats().percent(), 100.0);
+    }
+}
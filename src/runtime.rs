@@ -0,0 +1,222 @@
+use super::commands::absolute;
+use super::fingerprint::read_dir_sorted;
+use super::metadata::Metadata;
+use std::{env, path::Path, process::Command};
+
+// Debuggers and profilers `run` can start the app under
+const WRAPPERS: &[(&str, &[&str])] = &[
+    ("--gdb", &["gdb", "--args"]),
+    ("--lldb", &["lldb", "--"]),
+    ("--valgrind", &["valgrind"]),
+    ("--strace", &["strace", "-f"]),
+    ("--perf", &["perf", "record", "-g"]),
+];
+
+// How `run` launches the app
+#[derive(Debug, Default)]
+pub struct Launch {
+    pub memory_settings: bool,
+    pub wrapper: Option<&'static [&'static str]>,
+    // From --env, --inspector, --messages, --log and --lang
+    pub env: Vec<(String, String)>,
+    // Locale from --lang, e.g. de or en@pseudo
+    pub lang: Option<String>,
+}
+
+impl Launch {
+    // Take our flags out of flags, the rest are for cargo install
+    pub fn parse(flags: &[String]) -> Option<(Launch, Vec<String>)> {
+        let mut launch = Launch::default();
+        let mut rest = Vec::new();
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            match flag.as_str() {
+                "--memory-settings" => launch.memory_settings = true,
+                "--inspector" => launch.set("GTK_DEBUG", "interactive"),
+                "--messages" => launch.set("G_MESSAGES_DEBUG", "all"),
+                "--log" => launch.set("RUST_LOG", flags.next()?),
+                "--lang" => {
+                    let lang = flags.next()?;
+                    launch.set("LANGUAGE", lang);
+                    // gettext ignores LANGUAGE in the C locale
+                    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                        .iter()
+                        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()));
+                    if matches!(locale.as_deref(), None | Some("C") | Some("POSIX")) {
+                        launch.set("LC_ALL", "C.UTF-8");
+                    }
+                    launch.lang = Some(lang.clone());
+                }
+                "--env" => {
+                    let var = flags.next()?;
+                    let eq = var.find('=')?;
+                    launch.set(&var[..eq], &var[eq + 1..]);
+                }
+                flag => match WRAPPERS.iter().find(|(name, _)| *name == flag) {
+                    Some((_, wrapper)) => launch.wrapper = Some(wrapper),
+                    None => rest.push(flag.to_string()),
+                },
+            }
+        }
+
+        // Otherwise every GSlice allocation shows up as a leak
+        if launch.wrapper == Some(&["valgrind"]) {
+            launch.set("G_SLICE", "always-malloc");
+            launch.set("G_DEBUG", "gc-friendly");
+        }
+        Some((launch, rest))
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+    }
+
+    // binary with args, wrapped if asked to, in the app's environment
+    pub fn command(&self, binary: &Path, args: &[String], vars: &[(&str, String)]) -> Command {
+        let mut command = match self.wrapper {
+            Some(wrapper) => {
+                let mut command = Command::new(wrapper[0]);
+                command.args(&wrapper[1..]).arg(binary);
+                command
+            }
+            None => Command::new(binary),
+        };
+        command
+            .args(args)
+            .envs(vars.iter().map(|(key, value)| (key, value)))
+            .envs(self.env.iter().map(|(key, value)| (key, value)));
+        command
+    }
+}
+
+// Languages with a catalog for the app under prefix/share/locale
+pub fn languages(metadata: &Metadata, prefix: &Path) -> Vec<String> {
+    let mo = metadata.gettext_domain.clone() + ".mo";
+    read_dir_sorted(&prefix.join("share/locale"))
+        .into_iter()
+        .filter(|dir| dir.join("LC_MESSAGES").join(&mo).exists())
+        .filter_map(|dir| Some(dir.file_name()?.to_str()?.to_string()))
+        .collect()
+}
+
+// Environment matching what Builder::build installed to prefix,
+// used by `run` and printed or spawned by `env` and `shell`.
+pub fn variables(
+    metadata: &Metadata,
+    profile: &str,
+    prefix: &Path,
+    memory_settings: bool,
+) -> Vec<(&'static str, String)> {
+    let prefix = absolute(prefix);
+    let share = prefix.join("share");
+
+    let mut vars = vec![
+        ("PATH", prepend("PATH", &prefix.join("bin"), "")),
+        (
+            "GSETTINGS_SCHEMA_DIR",
+            prepend("GSETTINGS_SCHEMA_DIR", &share.join("glib-2.0/schemas"), ""),
+        ),
+        (
+            // Spec says to use /usr/local/share:/usr/share when unset
+            "XDG_DATA_DIRS",
+            prepend("XDG_DATA_DIRS", &share, "/usr/local/share:/usr/share"),
+        ),
+        // Same as LOCALEDIR in config.rs, TEXTDOMAINDIR is
+        // what gettext.sh and other tools look at.
+        (
+            "LOCALEDIR",
+            share.join("locale").to_string_lossy().into_owned(),
+        ),
+        (
+            "TEXTDOMAINDIR",
+            share.join("locale").to_string_lossy().into_owned(),
+        ),
+        ("TEXTDOMAIN", metadata.gettext_domain.clone()),
+        (
+            "CONFIG_PATH",
+            absolute(&metadata.targetdir.join(profile).join("data/config.rs"))
+                .to_string_lossy()
+                .into_owned(),
+        ),
+    ];
+    // Keep settings changed during the run out of dconf
+    if memory_settings {
+        vars.push(("GSETTINGS_BACKEND", "memory".to_string()));
+    }
+    vars
+}
+
+// vars as sh, fish or json, e.g. for eval "$(cargo pkg env _build)"
+pub fn format(vars: &[(&str, String)], format: &str) -> Option<String> {
+    match format {
+        "sh" => Some(
+            vars.iter()
+                .map(|(key, value)| format!("export {}={}\n", key, sh_quote(value)))
+                .collect(),
+        ),
+        // fish keeps *PATH variables as lists
+        "fish" => Some(
+            vars.iter()
+                .map(|(key, value)| {
+                    let values = if key.ends_with("PATH") {
+                        value.split(':').map(fish_quote).collect::<Vec<_>>()
+                    } else {
+                        vec![fish_quote(value)]
+                    };
+                    format!("set -gx {} {}\n", key, values.join(" "))
+                })
+                .collect(),
+        ),
+        "json" => Some(format!(
+            "{{\n{}\n}}\n",
+            vars.iter()
+                .map(|(key, value)| format!("  {}: {}", json_quote(key), json_quote(value)))
+                .collect::<Vec<_>>()
+                .join(",\n")
+        )),
+        _ => None,
+    }
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+pub(crate) fn json_quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Put dir in front of a colon separated list, once
+fn prepend(var: &str, dir: &Path, default: &str) -> String {
+    let dir = dir.to_string_lossy();
+    let current = env::var(var)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| default.to_string());
+    Some(dir.as_ref())
+        .into_iter()
+        .chain(
+            current
+                .split(':')
+                .filter(|path| !path.is_empty() && *path != dir),
+        )
+        .collect::<Vec<_>>()
+        .join(":")
+}
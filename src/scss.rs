@@ -0,0 +1,1123 @@
+use super::commands::absolute;
+use super::gresource::{walk, GResource};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+// Extra stylesheets written for a stylesheet reading $variant,
+// libadwaita loads style-dark.css and style-hc.css on its own.
+const VARIANTS: &[(&str, &str)] = &[("dark", "-dark"), ("hc", "-hc")];
+
+// .scss files under sourcedir, partials included
+pub fn sources(sourcedir: &Path) -> Vec<PathBuf> {
+    walk(sourcedir)
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("scss")))
+        .collect()
+}
+
+// _colors.scss is only imported by other stylesheets
+pub fn is_partial(path: &Path) -> bool {
+    let name = path.file_name().and_then(OsStr::to_str).unwrap_or("");
+    name.starts_with('_') && name.ends_with(".scss")
+}
+
+// sourcedir/style.scss compiles to outdir/style.css, its dark variant to style-dark.css
+pub fn target(sourcedir: &Path, outdir: &Path, source: &Path, suffix: &str) -> PathBuf {
+    let stem = source
+        .strip_prefix(sourcedir)
+        .unwrap_or(source)
+        .with_extension("");
+    outdir.join(format!("{}{}.css", stem.to_string_lossy(), suffix))
+}
+
+// A style-dark.scss next to style.scss replaces the generated variant
+fn has_own_source(source: &Path, suffix: &str) -> bool {
+    let stem = source.with_extension("");
+    PathBuf::from(format!("{}{}.scss", stem.to_string_lossy(), suffix)).exists()
+}
+
+// What compile() wrote for sources, variants only if they exist
+pub fn targets(sourcedir: &Path, outdir: &Path, sources: &[PathBuf]) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+    for source in sources.iter().filter(|source| !is_partial(source)) {
+        targets.push(target(sourcedir, outdir, source, ""));
+        for (_, suffix) in VARIANTS {
+            let target = target(sourcedir, outdir, source, suffix);
+            if target.exists() && !has_own_source(source, suffix) {
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+pub fn compile(sourcedir: &Path, outdir: &Path, sources: &[PathBuf]) -> Option<()> {
+    for source in sources.iter().filter(|source| !is_partial(source)) {
+        let write = |suffix: &str, variant: &str| {
+            let target = target(sourcedir, outdir, source, suffix);
+            match Compiler::compile(source, variant) {
+                Ok((css, reads_variant)) => {
+                    std::fs::create_dir_all(target.parent()?).ok()?;
+                    std::fs::write(&target, css).ok()?;
+                    Some(reads_variant)
+                }
+                Err(err) => {
+                    println!("\x1b[1;31merror\x1b[0m: {}", err);
+                    None
+                }
+            }
+        };
+
+        // Only stylesheets using $variant get variants
+        let reads_variant = write("", "light")?;
+        for (variant, suffix) in VARIANTS {
+            if has_own_source(source, suffix) {
+                continue;
+            }
+            if reads_variant {
+                write(suffix, variant)?;
+            } else {
+                std::fs::remove_file(target(sourcedir, outdir, source, suffix)).ok();
+            }
+        }
+    }
+    Some(())
+}
+
+// Point .scss entries at the compiled .css, style.scss is
+// bundled as style.css and its variants next to it.
+pub fn rewrite(gresource: &mut GResource, sourcedir: &Path, outdir: &Path) {
+    let mut files = Vec::new();
+    for file in gresource.files.drain(..) {
+        let stem = match file.path.strip_suffix(".scss") {
+            Some(stem) => stem.to_string(),
+            None => {
+                files.push(file);
+                continue;
+            }
+        };
+        let alias = file.alias.clone().unwrap_or(stem + ".css");
+        let alias = alias.strip_suffix(".css").unwrap_or(&alias).to_string();
+        let source = sourcedir.join(&file.path);
+
+        for suffix in Some("").into_iter().chain(VARIANTS.iter().map(|(_, s)| *s)) {
+            let target = target(sourcedir, outdir, &source, suffix);
+            if suffix.is_empty() || (target.exists() && !has_own_source(&source, suffix)) {
+                let mut compiled = file.clone();
+                compiled.path = absolute(&target).to_string_lossy().into_owned();
+                compiled.alias = Some(format!("{}{}.css", alias, suffix));
+                files.push(compiled);
+            }
+        }
+    }
+    gresource.files = files;
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    line: usize,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    // $name: value, with !default or !global
+    Variable {
+        name: String,
+        value: String,
+        default: bool,
+        global: bool,
+    },
+    Declaration {
+        name: String,
+        value: String,
+    },
+    Rule {
+        selector: String,
+        children: Vec<Node>,
+    },
+    // @name params, followed by a block or ;
+    At {
+        name: String,
+        params: String,
+        block: Option<Vec<Node>>,
+    },
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    file: &'a Path,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(file: &Path) -> Result<Vec<Node>, String> {
+        let text =
+            std::fs::read_to_string(file).map_err(|err| format!("{}: {}", file.display(), err))?;
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+            file,
+        };
+        parser.block(false)
+    }
+
+    fn error(&self, line: usize, message: &str) -> String {
+        format!("{}:{}: {}", self.file.display(), line, message)
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    // Whitespace and comments between statements
+    fn skip(&mut self) {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    while !matches!(self.peek(0), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => self.comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn comment(&mut self) {
+        self.pos += 2;
+        while self.peek(0).is_some() && !(self.peek(0) == Some('*') && self.peek(1) == Some('/')) {
+            self.bump();
+        }
+        self.pos += 2;
+    }
+
+    fn block(&mut self, nested: bool) -> Result<Vec<Node>, String> {
+        let start = self.line;
+        let mut nodes = Vec::new();
+        loop {
+            self.skip();
+            match self.peek(0) {
+                None if nested => return Err(self.error(start, "unclosed block")),
+                None => return Ok(nodes),
+                Some('}') if nested => {
+                    self.bump();
+                    return Ok(nodes);
+                }
+                Some('}') => return Err(self.error(self.line, "unexpected }")),
+                _ => (),
+            }
+
+            let line = self.line;
+            let (text, end) = self.statement();
+            let text = text.trim();
+            let kind = if end == Some('{') {
+                let children = self.block(true)?;
+                match text.strip_prefix('@') {
+                    Some(at) => at_rule(at, Some(children)),
+                    None => Kind::Rule {
+                        selector: text.to_string(),
+                        children,
+                    },
+                }
+            } else if text.is_empty() {
+                continue;
+            } else if let Some(at) = text.strip_prefix('@') {
+                at_rule(at, None)
+            } else {
+                let colon = match text.find(':') {
+                    Some(colon) => colon,
+                    None => {
+                        return Err(self
+                            .error(line, &format!("expected a declaration, found \"{}\"", text)))
+                    }
+                };
+                let (name, value) = (text[..colon].trim(), text[colon + 1..].trim());
+                match name.strip_prefix('$') {
+                    Some(name) => {
+                        let default = value.contains("!default");
+                        let global = value.contains("!global");
+                        let value = value.replace("!default", "").replace("!global", "");
+                        Kind::Variable {
+                            name: name.to_string(),
+                            value: value.trim().to_string(),
+                            default,
+                            global,
+                        }
+                    }
+                    None => Kind::Declaration {
+                        name: name.to_string(),
+                        value: value.to_string(),
+                    },
+                }
+            };
+            nodes.push(Node { line, kind });
+        }
+    }
+
+    // Text up to the next {, ; or } outside of strings,
+    // parentheses and #{}, the closing } isn't consumed.
+    fn statement(&mut self) -> (String, Option<char>) {
+        let mut text = String::new();
+        let mut depth = 0;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '"' | '\'' => {
+                    text.push(c);
+                    self.bump();
+                    while let Some(next) = self.bump() {
+                        text.push(next);
+                        if next == '\\' {
+                            text.extend(self.bump());
+                        } else if next == c {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                '#' if self.peek(1) == Some('{') => {
+                    while let Some(next) = self.bump() {
+                        text.push(next);
+                        if next == '}' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                '/' if self.peek(1) == Some('*') => {
+                    self.comment();
+                    continue;
+                }
+                // Not a comment inside url(http://...)
+                '/' if self.peek(1) == Some('/') && depth == 0 => {
+                    while !matches!(self.peek(0), None | Some('\n')) {
+                        self.bump();
+                    }
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                '{' | ';' if depth == 0 => {
+                    self.bump();
+                    return (text, Some(c));
+                }
+                '}' if depth == 0 => return (text, Some(c)),
+                _ => (),
+            }
+            text.push(c);
+            self.bump();
+        }
+        (text, None)
+    }
+}
+
+fn at_rule(text: &str, block: Option<Vec<Node>>) -> Kind {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(text.len());
+    Kind::At {
+        name: text[..end].to_string(),
+        params: text[end..].trim().to_string(),
+        block,
+    }
+}
+
+struct Mixin {
+    params: Vec<(String, Option<String>)>,
+    body: Vec<Node>,
+    file: PathBuf,
+}
+
+// Compiles the subset of SCSS stylesheets for GTK use: variables,
+// nesting with &, @import/@use of partials, @mixin/@include with
+// @content, @if/@else, #{} interpolation and arithmetic on numbers.
+// Functions are left alone, GTK has alpha(), mix() and shade().
+struct Compiler {
+    file: PathBuf,
+    variant: String,
+    reads_variant: bool,
+    scopes: Vec<HashMap<String, String>>,
+    mixins: HashMap<String, Mixin>,
+    imports: Vec<PathBuf>,
+    contents: Vec<Option<(Vec<Node>, PathBuf)>>,
+    out: String,
+}
+
+impl Compiler {
+    // CSS for file and whether it read $variant
+    fn compile(file: &Path, variant: &str) -> Result<(String, bool), String> {
+        let mut compiler = Compiler {
+            file: file.to_path_buf(),
+            variant: variant.to_string(),
+            reads_variant: false,
+            scopes: vec![HashMap::new()],
+            mixins: HashMap::new(),
+            imports: vec![file.to_path_buf()],
+            contents: Vec::new(),
+            out: String::new(),
+        };
+        let nodes = Parser::parse(file)?;
+        compiler.nodes(&nodes, &[], &mut Vec::new())?;
+        Ok((compiler.out, compiler.reads_variant))
+    }
+
+    fn error(&self, line: usize, message: &str) -> String {
+        format!("{}:{}: {}", self.file.display(), line, message)
+    }
+
+    fn nodes(
+        &mut self,
+        nodes: &[Node],
+        selectors: &[String],
+        declarations: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = &nodes[i];
+            i += 1;
+            match &node.kind {
+                Kind::Variable {
+                    name,
+                    value,
+                    default,
+                    global,
+                } => {
+                    if *default && self.lookup(name).is_some() {
+                        continue;
+                    }
+                    let value = self.value(value, node.line)?;
+                    let scope = if *global { 0 } else { self.scopes.len() - 1 };
+                    self.scopes[scope].insert(name.clone(), value);
+                }
+                Kind::Declaration { name, value } => {
+                    if selectors.is_empty() {
+                        return Err(self.error(node.line, "declarations must be inside a rule"));
+                    }
+                    let name = self.value(name, node.line)?;
+                    let value = self.value(value, node.line)?;
+                    declarations.push(format!("{}: {}", name, value));
+                }
+                Kind::Rule { selector, children } => {
+                    let selector = self.value(selector, node.line)?;
+                    let nested = combine(selectors, &selector);
+                    self.scopes.push(HashMap::new());
+                    let result = self.rule(&nested, children);
+                    self.scopes.pop();
+                    result?;
+                }
+                Kind::At {
+                    name,
+                    params,
+                    block,
+                } => match (name.as_str(), block) {
+                    ("import", None) | ("use", None) | ("forward", None) => {
+                        self.import(name, params, node.line, selectors, declarations)?
+                    }
+                    ("mixin", Some(body)) => {
+                        let (name, params) = call(params);
+                        let params = params
+                            .iter()
+                            .map(|param| match param.find(':') {
+                                Some(colon) => (
+                                    param[..colon].trim().trim_start_matches('$').to_string(),
+                                    Some(param[colon + 1..].trim().to_string()),
+                                ),
+                                None => (param.trim_start_matches('$').to_string(), None),
+                            })
+                            .collect();
+                        self.mixins.insert(
+                            name,
+                            Mixin {
+                                params,
+                                body: body.clone(),
+                                file: self.file.clone(),
+                            },
+                        );
+                    }
+                    ("include", block) => {
+                        let content = block.clone().map(|block| (block, self.file.clone()));
+                        self.include(params, content, node.line, selectors, declarations)?
+                    }
+                    ("content", None) => {
+                        if let Some(Some((content, file))) = self.contents.last().cloned() {
+                            let file = std::mem::replace(&mut self.file, file);
+                            let result = self.nodes(&content, selectors, declarations);
+                            self.file = file;
+                            result?;
+                        }
+                    }
+                    ("if", Some(body)) => {
+                        // Take the whole @if/@else if/@else chain
+                        let mut branches = vec![(Some(params.clone()), body)];
+                        while let Some(Node {
+                            kind:
+                                Kind::At {
+                                    name,
+                                    params,
+                                    block: Some(block),
+                                },
+                            ..
+                        }) = nodes.get(i)
+                        {
+                            if name != "else" {
+                                break;
+                            }
+                            let condition = params.strip_prefix("if").map(|c| c.trim().to_string());
+                            branches.push((condition, block));
+                            i += 1;
+                        }
+                        for (condition, body) in branches {
+                            let taken = match condition {
+                                Some(condition) => self.condition(&condition, node.line)?,
+                                None => true,
+                            };
+                            if taken {
+                                self.nodes(body, selectors, declarations)?;
+                                break;
+                            }
+                        }
+                    }
+                    ("else", _) => return Err(self.error(node.line, "@else without @if")),
+                    ("debug", None) | ("warn", None) => {
+                        let message = self.value(params, node.line)?;
+                        println!(
+                            "\x1b[1;33mwarning\x1b[0m: {}",
+                            self.error(node.line, &message)
+                        );
+                    }
+                    ("error", None) => {
+                        let message = self.value(params, node.line)?;
+                        return Err(self.error(node.line, &message));
+                    }
+                    ("function", _)
+                    | ("return", _)
+                    | ("each", _)
+                    | ("for", _)
+                    | ("while", _)
+                    | ("extend", _)
+                    | ("at-root", _) => {
+                        return Err(self.error(node.line, &format!("@{} isn't supported", name)))
+                    }
+                    (_, _) if !selectors.is_empty() => {
+                        return Err(self.error(
+                            node.line,
+                            &format!("@{} inside a rule isn't supported", name),
+                        ))
+                    }
+                    // Plain CSS like @keyframes and @define-color
+                    (_, Some(children)) => {
+                        let params = self.value(params, node.line)?;
+                        let outer = std::mem::take(&mut self.out);
+                        self.scopes.push(HashMap::new());
+                        let result = self.nodes(children, &[], &mut Vec::new());
+                        self.scopes.pop();
+                        let inner = std::mem::replace(&mut self.out, outer);
+                        result?;
+
+                        self.out.push_str(&format!("@{} {} {{\n", name, params));
+                        for line in inner.trim_end().lines() {
+                            if !line.is_empty() {
+                                self.out.push_str("  ");
+                            }
+                            self.out.push_str(line);
+                            self.out.push('\n');
+                        }
+                        self.out.push_str("}\n\n");
+                    }
+                    (_, None) => {
+                        let params = self.value(params, node.line)?;
+                        self.out.push_str(&format!("@{} {};\n\n", name, params));
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Declarations of a rule come before its nested rules
+    fn rule(&mut self, selectors: &[String], children: &[Node]) -> Result<(), String> {
+        let outer = std::mem::take(&mut self.out);
+        let mut declarations = Vec::new();
+        let result = self.nodes(children, selectors, &mut declarations);
+        let nested = std::mem::replace(&mut self.out, outer);
+        result?;
+
+        if !declarations.is_empty() {
+            self.out.push_str(&selectors.join(",\n"));
+            self.out.push_str(" {\n");
+            for declaration in declarations {
+                self.out.push_str(&format!("  {};\n", declaration));
+            }
+            self.out.push_str("}\n\n");
+        }
+        self.out.push_str(&nested);
+        Ok(())
+    }
+
+    fn import(
+        &mut self,
+        rule: &str,
+        params: &str,
+        line: usize,
+        selectors: &[String],
+        declarations: &mut Vec<String>,
+    ) -> Result<(), String> {
+        for name in split(params, ',') {
+            // @use "colors" as c, namespaces are dropped on lookup
+            let name = name.split(" as ").next().unwrap_or(&name);
+            let name = name.split(" with ").next().unwrap_or(name).trim();
+            let name = name.trim_matches(|c| c == '"' || c == '\'');
+            if name.starts_with("sass:") {
+                continue;
+            }
+            // Plain CSS imports are kept
+            if name.ends_with(".css") || name.starts_with("url(") {
+                self.out.push_str(&format!("@import {};\n\n", params));
+                return Ok(());
+            }
+
+            let path = self
+                .resolve(name)
+                .ok_or_else(|| self.error(line, &format!("can't find stylesheet \"{}\"", name)))?;
+            if self.imports.contains(&path) {
+                if rule == "import" {
+                    return Err(self.error(line, &format!("{} imports itself", path.display())));
+                }
+                continue;
+            }
+
+            let nodes = Parser::parse(&path)?;
+            let file = std::mem::replace(&mut self.file, path.clone());
+            self.imports.push(path);
+            let result = self.nodes(&nodes, selectors, declarations);
+            self.file = file;
+            result?;
+        }
+        Ok(())
+    }
+
+    // colors finds colors.scss or _colors.scss next to the current file
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let path = self.file.parent()?.join(name);
+        let dir = path.parent()?;
+        let name = path.file_name()?.to_str()?;
+        [
+            format!("{}.scss", name),
+            format!("_{}.scss", name),
+            name.to_string(),
+        ]
+        .iter()
+        .map(|candidate| dir.join(candidate))
+        .find(|candidate| candidate.is_file() && candidate.extension() == Some(OsStr::new("scss")))
+    }
+
+    fn include(
+        &mut self,
+        params: &str,
+        content: Option<(Vec<Node>, PathBuf)>,
+        line: usize,
+        selectors: &[String],
+        declarations: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let (name, args) = call(params);
+        let name = name.rsplit('.').next().unwrap_or(&name).to_string();
+        let (params, body, file) = match self.mixins.get(&name) {
+            Some(mixin) => (mixin.params.clone(), mixin.body.clone(), mixin.file.clone()),
+            None => return Err(self.error(line, &format!("undefined mixin {}", name))),
+        };
+
+        // Arguments are evaluated where the mixin is included
+        let mut positional = Vec::new();
+        let mut named = HashMap::new();
+        for arg in &args {
+            match arg.trim().strip_prefix('$').and_then(|arg| {
+                let colon = arg.find(':')?;
+                Some((
+                    arg[..colon].trim().to_string(),
+                    arg[colon + 1..].to_string(),
+                ))
+            }) {
+                Some((name, value)) => {
+                    named.insert(name, self.value(&value, line)?);
+                }
+                None => positional.push(self.value(arg, line)?),
+            }
+        }
+
+        let file = std::mem::replace(&mut self.file, file);
+        self.scopes.push(HashMap::new());
+        self.contents.push(content);
+        let mut result = Ok(());
+        for (i, (param, default)) in params.iter().enumerate() {
+            let value = match (positional.get(i), named.remove(param), default) {
+                (Some(value), _, _) => value.clone(),
+                (None, Some(value), _) => value,
+                (None, None, Some(default)) => match self.value(default, line) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                },
+                (None, None, None) => {
+                    result = Err(format!(
+                        "{}:{}: missing argument ${} for mixin {}",
+                        file.display(),
+                        line,
+                        param,
+                        name
+                    ));
+                    break;
+                }
+            };
+            self.scopes.last_mut().unwrap().insert(param.clone(), value);
+        }
+        if result.is_ok() {
+            result = self.nodes(&body, selectors, declarations);
+        }
+        self.contents.pop();
+        self.scopes.pop();
+        self.file = file;
+        result
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<String> {
+        if name == "variant" {
+            self.reads_variant = true;
+        }
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .or_else(|| Some(format!("\"{}\"", self.variant)).filter(|_| name == "variant"))
+    }
+
+    // Text with #{} and variables replaced and arithmetic done
+    fn value(&mut self, text: &str, line: usize) -> Result<String, String> {
+        let mut out = String::new();
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut quote = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '#' && chars.get(i + 1) == Some(&'{') {
+                let end = (i..chars.len())
+                    .find(|&end| chars[end] == '}')
+                    .ok_or_else(|| self.error(line, "unclosed #{"))?;
+                let inner = chars[i + 2..end].iter().collect::<String>();
+                let value = self.value(&inner, line)?;
+                out.push_str(unquote(&value));
+                i = end + 1;
+                continue;
+            }
+            match (quote, c) {
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (Some(q), c) if q == c => quote = None,
+                (None, '$') => {
+                    let end = (i + 1..chars.len())
+                        .find(|&end| !is_ident(chars[end]))
+                        .unwrap_or(chars.len());
+                    let name = chars[i + 1..end].iter().collect::<String>();
+                    // colors.$accent from @use "colors"
+                    if out.ends_with('.') {
+                        let keep = out[..out.len() - 1].trim_end_matches(is_ident).len();
+                        out.truncate(keep);
+                    }
+                    let value = self.lookup(&name).ok_or_else(|| {
+                        self.error(line, &format!("undefined variable ${}", name))
+                    })?;
+                    out.push_str(&value);
+                    i = end;
+                    continue;
+                }
+                _ => (),
+            }
+            out.push(c);
+            i += 1;
+        }
+        Ok(arithmetic(out.trim(), divides(text)))
+    }
+
+    fn condition(&mut self, text: &str, line: usize) -> Result<bool, String> {
+        let text = self.value(text, line)?;
+        Ok(text.split(" or ").any(|any| any.split(" and ").all(test)))
+    }
+}
+
+fn test(text: &str) -> bool {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+        .unwrap_or(text);
+    if let Some(text) = text.strip_prefix("not ") {
+        return !test(text);
+    }
+    for op in &["==", "!=", "<=", ">=", "<", ">"] {
+        if let Some(at) = text.find(op) {
+            let (a, b) = (text[..at].trim(), text[at + op.len()..].trim());
+            let (a, b) = (unquote(a), unquote(b));
+            return match (*op, number(a), number(b)) {
+                ("==", _, _) => a == b,
+                ("!=", _, _) => a != b,
+                ("<=", Some((a, _)), Some((b, _))) => a <= b,
+                (">=", Some((a, _)), Some((b, _))) => a >= b,
+                ("<", Some((a, _)), Some((b, _))) => a < b,
+                (">", Some((a, _)), Some((b, _))) => a > b,
+                _ => false,
+            };
+        }
+    }
+    !matches!(text, "false" | "null" | "")
+}
+
+// Selectors nested in parents, & stands for the parent
+fn combine(parents: &[String], selector: &str) -> Vec<String> {
+    let children = split(selector, ',')
+        .into_iter()
+        .map(|child| child.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|child| !child.is_empty())
+        .collect::<Vec<_>>();
+    if parents.is_empty() {
+        return children;
+    }
+    parents
+        .iter()
+        .flat_map(|parent| {
+            children.iter().map(move |child| {
+                if child.contains('&') {
+                    child.replace('&', parent)
+                } else {
+                    format!("{} {}", parent, child)
+                }
+            })
+        })
+        .collect()
+}
+
+// name(a, b) to name and its arguments
+fn call(text: &str) -> (String, Vec<String>) {
+    match text.find('(') {
+        Some(open) => {
+            let close = text.rfind(')').unwrap_or(text.len());
+            let args = split(&text[open + 1..close.max(open + 1)], ',')
+                .into_iter()
+                .map(|arg| arg.trim().to_string())
+                .filter(|arg| !arg.is_empty())
+                .collect();
+            (text[..open].trim().to_string(), args)
+        }
+        None => (text.trim().to_string(), Vec::new()),
+    }
+}
+
+// Split on separator outside of strings and parentheses
+fn split(text: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut depth = 0;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => (),
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    if text.len() >= 2
+        && (text.starts_with('"') && text.ends_with('"')
+            || text.starts_with('\'') && text.ends_with('\''))
+    {
+        &text[1..text.len() - 1]
+    } else {
+        text
+    }
+}
+
+// 12px to (12.0, "px")
+fn number(text: &str) -> Option<(f64, &str)> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let len = digits
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(digits.len());
+    let (value, unit) = text.split_at(text.len() - digits.len() + len);
+    if len == 0 || !unit.chars().all(|c| c.is_ascii_alphabetic() || c == '%') {
+        return None;
+    }
+    Some((value.parse().ok()?, unit))
+}
+
+fn format_number(value: f64, unit: &str) -> String {
+    let text = format!("{:.5}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    let text = if text == "-0" { "0" } else { text };
+    text.to_string() + unit
+}
+
+fn operate(a: &str, op: &str, b: &str) -> Option<String> {
+    let ((x, xunit), (y, yunit)) = (number(a)?, number(b)?);
+    let unit = if xunit.is_empty() { yunit } else { xunit };
+    let mixed = !xunit.is_empty() && !yunit.is_empty();
+    match op {
+        "+" | "-" if mixed && xunit != yunit => None,
+        "+" => Some(format_number(x + y, unit)),
+        "-" => Some(format_number(x - y, unit)),
+        "*" if mixed => None,
+        "*" => Some(format_number(x * y, unit)),
+        "/" if y == 0.0 => None,
+        "/" if xunit == yunit => Some(format_number(x / y, "")),
+        "/" if yunit.is_empty() => Some(format_number(x / y, xunit)),
+        _ => None,
+    }
+}
+
+// Like Sass, a / b only divides when a variable or function result
+// is involved, font: 12px / 1.5 keeps the slash as a separator.
+fn divides(text: &str) -> bool {
+    let tokens = split(text, ' ')
+        .into_iter()
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>();
+    tokens.windows(3).any(|tokens| {
+        tokens[1] == "/"
+            && [&tokens[0], &tokens[2]]
+                .iter()
+                .any(|token| token.contains('$') || token.ends_with(')'))
+    })
+}
+
+// Evaluate *, /, + and - between numbers, a / b only if divide
+// or in parentheses. Anything else like 1px solid or
+// alpha(black, 0.5) stays as is.
+fn arithmetic(text: &str, divide: bool) -> String {
+    let text = groups(text, divide);
+    let mut tokens = split(&text, ' ')
+        .into_iter()
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>();
+    for ops in &[["*", "/"], ["+", "-"]] {
+        let mut i = 1;
+        while i + 1 < tokens.len() {
+            if ops.contains(&tokens[i].as_str()) && (divide || tokens[i] != "/") {
+                if let Some(result) = operate(&tokens[i - 1], &tokens[i], &tokens[i + 1]) {
+                    tokens.splice(i - 1..=i + 1, Some(result));
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+    tokens.join(" ")
+}
+
+// (a + b) without a function name in front is evaluated
+fn groups(text: &str, divide: bool) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let mut depth = 0;
+        let close = match rest[open..].char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => (),
+            }
+            Some(open + i).filter(|_| depth == 0)
+        }) {
+            Some(close) => close,
+            None => break,
+        };
+
+        let name = &rest[..open];
+        let inner = &rest[open + 1..close];
+        out.push_str(name);
+        if name.ends_with("url") {
+            out.push_str(&rest[open..=close]);
+        } else if name.ends_with(is_ident) {
+            let args = split(inner, ',')
+                .iter()
+                .map(|arg| arithmetic(arg.trim(), divide))
+                .collect::<Vec<_>>();
+            out.push_str(&format!("({})", args.join(", ")));
+        } else {
+            let value = arithmetic(inner, true);
+            if number(&value).is_some() {
+                out.push_str(&value);
+            } else {
+                out.push_str(&format!("({})", value));
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // A fresh directory holding files
+    fn sourcedir(files: &[(&str, &str)]) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "cargo-pkg-scss-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        dir
+    }
+
+    // CSS for style.scss next to files, or the error
+    fn css(style: &str, files: &[(&str, &str)]) -> Result<String, String> {
+        let dir = sourcedir(files);
+        std::fs::write(dir.join("style.scss"), style).unwrap();
+        let result = Compiler::compile(&dir.join("style.scss"), "light").map(|(css, _)| css);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    fn error(style: &str) -> String {
+        css(style, &[]).expect_err("stylesheet compiles")
+    }
+
+    #[test]
+    fn variables() {
+        let style =
+            "$fg: red;\n$pad: 4px;\n$fg: blue !default;\n.a { color: $fg; padding: $pad * 2; }\n";
+        assert_eq!(
+            css(style, &[]).unwrap(),
+            ".a {\n  color: red;\n  padding: 8px;\n}\n\n"
+        );
+        assert!(error(".a { color: $fg; }").ends_with("undefined variable $fg"));
+    }
+
+    #[test]
+    fn division() {
+        // A plain slash separates values
+        let style = ".a { border-radius: 8px / 4px; font: 12px / 1.5 sans; }\n";
+        assert_eq!(
+            css(style, &[]).unwrap(),
+            ".a {\n  border-radius: 8px / 4px;\n  font: 12px / 1.5 sans;\n}\n\n"
+        );
+        let style = "$pad: 12px;\n.a { margin: (8px / 2); padding: $pad / 3 1px; }\n";
+        assert_eq!(
+            css(style, &[]).unwrap(),
+            ".a {\n  margin: 4px;\n  padding: 4px 1px;\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn nesting() {
+        let style = ".a {\n  color: red;\n  &:hover { color: blue; }\n  .b, .c { margin: 0; }\n}\n";
+        assert_eq!(
+            css(style, &[]).unwrap(),
+            ".a {\n  color: red;\n}\n\n.a:hover {\n  color: blue;\n}\n\n.a .b,\n.a .c {\n  margin: 0;\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn imports() {
+        let partial = ("_colors.scss", "$accent: #3584e4;\n");
+        let style = "@import \"colors\";\n.a { color: $accent; }\n";
+        assert_eq!(
+            css(style, &[partial]).unwrap(),
+            ".a {\n  color: #3584e4;\n}\n\n"
+        );
+        assert!(error("@import \"missing\";").ends_with("can't find stylesheet \"missing\""));
+    }
+
+    #[test]
+    fn mixins() {
+        let style = "@mixin card($radius: 6px) {\n  border-radius: $radius;\n  @content;\n}\n.a { @include card { color: red; } }\n.b { @include card(2px); }\n";
+        assert_eq!(
+            css(style, &[]).unwrap(),
+            ".a {\n  border-radius: 6px;\n  color: red;\n}\n\n.b {\n  border-radius: 2px;\n}\n\n"
+        );
+        assert!(error(".a { @include card; }").ends_with("undefined mixin card"));
+    }
+
+    #[test]
+    fn variants() {
+        let dir = sourcedir(&[
+            (
+                "style.scss",
+                "@if $variant == \"dark\" { .a { color: white; } } @else { .a { color: black; } }\n",
+            ),
+            ("plain.scss", ".b { color: red; }\n"),
+        ]);
+        let outdir = dir.join("out");
+        let sources = sources(&dir);
+        compile(&dir, &outdir, &sources).unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(outdir.join(name)).unwrap();
+        assert!(read("style.css").contains("color: black"));
+        assert!(read("style-dark.css").contains("color: white"));
+        assert!(read("style-hc.css").contains("color: black"));
+        assert!(outdir.join("plain.css").exists());
+        assert!(!outdir.join("plain-dark.css").exists());
+
+        let mut targets = targets(&dir, &outdir, &sources);
+        targets.sort();
+        let names = targets
+            .iter()
+            .map(|target| target.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["plain.css", "style-dark.css", "style-hc.css", "style.css"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(error("@function double($x) { @return $x * 2; }")
+            .ends_with("@function isn't supported"));
+        assert!(
+            error("@each $c in red, blue { .a { color: $c; } }").ends_with("@each isn't supported")
+        );
+        assert!(error(".a { @extend .b; }").ends_with("@extend isn't supported"));
+        assert!(error("@else { .a { color: red; } }").ends_with("@else without @if"));
+        assert!(error("color: red;").ends_with("declarations must be inside a rule"));
+        assert!(error(".a { color: red;").contains("unclosed"));
+    }
+}
@@ -0,0 +1,516 @@
+use super::gschema::{Enum, Key, Schema, SchemaList};
+
+// Typed wrappers around gio::Settings for the app's schemas,
+// emitted into config.rs as `pub mod settings`. Renaming or
+// retyping a key then fails to compile instead of at runtime.
+pub fn generate(schemas: &SchemaList, app_id: &str) -> String {
+    let mut code = String::from("\npub mod settings {\n");
+    for e in &schemas.enums {
+        code.push_str(&generate_enum(schemas, e));
+    }
+    for schema in &schemas.schemas {
+        code.push_str(&generate_schema(schemas, schema, app_id));
+    }
+    code.push_str("}\n");
+    code
+}
+
+// io.foo.Demo is Settings, io.foo.Demo.Window WindowSettings
+fn struct_name(id: &str, app_id: &str) -> String {
+    if id == app_id {
+        return "Settings".to_string();
+    }
+    let name = match id
+        .strip_prefix(app_id)
+        .and_then(|rest| rest.strip_prefix('.'))
+    {
+        Some(rest) => rest,
+        None => id.rsplit('.').next().unwrap_or(id),
+    };
+    camel_case(name) + "Settings"
+}
+
+// io.foo.Demo.Mode is Mode, unless io.foo.Demo.Window.Mode exists
+// as well, then they take more of their id: DemoMode and WindowMode
+fn enum_name(schemas: &SchemaList, id: &str) -> String {
+    let tail = |id: &str, parts: usize| {
+        let id = id.split('.').collect::<Vec<_>>();
+        camel_case(&id[id.len().saturating_sub(parts)..].join("."))
+    };
+    let parts = id.split('.').count();
+    for n in 1..parts {
+        let name = tail(id, n);
+        if schemas
+            .enums
+            .iter()
+            .all(|e| e.id == id || tail(&e.id, n) != name)
+        {
+            return name;
+        }
+    }
+    tail(id, parts)
+}
+
+fn generate_enum(schemas: &SchemaList, e: &Enum) -> String {
+    let name = enum_name(schemas, &e.id);
+    let ty = if e.is_flags { "u32" } else { "i32" };
+    let variants = e
+        .values
+        .iter()
+        .map(|(nick, value)| (variant_name(nick), value))
+        .collect::<Vec<_>>();
+
+    let mut code = format!(
+        "
+    #[allow(dead_code)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum {} {{\n",
+        name
+    );
+    for (variant, _) in &variants {
+        code.push_str(&format!("        {},\n", variant));
+    }
+    code.push_str(&format!(
+        "    }}
+
+    #[allow(dead_code)]
+    impl {name} {{
+        pub const ALL: &'static [{name}] = &[{all}];
+
+        pub fn value(self) -> {ty} {{
+            match self {{\n",
+        name = name,
+        ty = ty,
+        all = variants
+            .iter()
+            .map(|(variant, _)| format!("{}::{}", name, variant))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+    for (variant, value) in &variants {
+        code.push_str(&format!(
+            "                {}::{} => {},\n",
+            name, variant, value
+        ));
+    }
+    code.push_str("            }\n        }\n");
+
+    if !e.is_flags {
+        // Several nicks may share a value, the first one wins
+        code.push_str(&format!(
+            "
+        pub fn from_value(value: i32) -> Option<{}> {{
+            match value {{\n",
+            name
+        ));
+        let mut seen = Vec::new();
+        for (variant, value) in &variants {
+            if !seen.contains(value) {
+                code.push_str(&format!(
+                    "                {} => Some({}::{}),\n",
+                    value, name, variant
+                ));
+                seen.push(*value);
+            }
+        }
+        code.push_str("                _ => None,\n            }\n        }\n");
+    }
+    code.push_str("    }\n");
+    code
+}
+
+fn generate_schema(schemas: &SchemaList, schema: &Schema, app_id: &str) -> String {
+    let name = struct_name(&schema.id, app_id);
+
+    // Relocatable schemas have no fixed path
+    let constructor = match &schema.path {
+        Some(_) => format!(
+            "pub fn new() -> Self {{
+            {}(gio::Settings::new(\"{}\"))
+        }}",
+            name, schema.id
+        ),
+        None => format!(
+            "pub fn new(path: &str) -> Self {{
+            {}(gio::Settings::new_with_path(\"{}\", path))
+        }}",
+            name, schema.id
+        ),
+    };
+
+    let mut code = format!(
+        "
+    #[allow(dead_code)]
+    #[derive(Clone)]
+    pub struct {name}(gio::Settings);
+
+    #[allow(dead_code, clippy::new_without_default)]
+    impl {name} {{
+        pub const ID: &'static str = \"{id}\";
+
+        {constructor}\n",
+        name = name,
+        id = schema.id,
+        constructor = constructor
+    );
+
+    for key in schemas.keys(schema) {
+        code.push_str(&generate_key(schemas, &name, key));
+    }
+
+    code.push_str(&format!(
+        "    }}
+
+    impl std::ops::Deref for {name} {{
+        type Target = gio::Settings;
+
+        fn deref(&self) -> &gio::Settings {{
+            &self.0
+        }}
+    }}\n",
+        name = name
+    ));
+    code
+}
+
+fn generate_key(schemas: &SchemaList, name: &str, key: &Key) -> String {
+    let getter = method_name(&key.name);
+    let k = &key.name;
+
+    let (ty, get, set_ty, set) = match key.enum_id.as_ref().and_then(|id| schemas.enumeration(id)) {
+        Some(e) if e.is_flags => {
+            let e = enum_name(schemas, &e.id);
+            (
+                format!("Vec<{}>", e),
+                format!(
+                    "let bits = self.0.get_flags(\"{}\");
+            {}::ALL
+                .iter()
+                .copied()
+                .filter(|flag| bits & flag.value() != 0)
+                .collect()",
+                    k, e
+                ),
+                format!("&[{}]", e),
+                format!(
+                    "let bits = value.iter().fold(0, |bits, flag| bits | flag.value());
+            self.0.set_flags(\"{}\", bits)",
+                    k
+                ),
+            )
+        }
+        Some(e) => {
+            let e = enum_name(schemas, &e.id);
+            (
+                e.clone(),
+                format!(
+                    "{}::from_value(self.0.get_enum(\"{}\")).expect(\"Invalid enum value\")",
+                    e, k
+                ),
+                e,
+                format!("self.0.set_enum(\"{}\", value.value())", k),
+            )
+        }
+        None => {
+            let (ty, getter, set_ty) = match key.ty.as_str() {
+                "b" => ("bool", "boolean", "bool"),
+                "i" => ("i32", "int", "i32"),
+                "u" => ("u32", "uint", "u32"),
+                "x" => ("i64", "int64", "i64"),
+                "t" => ("u64", "uint64", "u64"),
+                "d" => ("f64", "double", "f64"),
+                "s" => ("String", "string", "&str"),
+                "as" => ("Vec<String>", "strv", "&[&str]"),
+                _ => ("glib::Variant", "value", "&glib::Variant"),
+            };
+            let get = match key.ty.as_str() {
+                "s" => format!("self.0.get_string(\"{}\").to_string()", k),
+                "as" => format!(
+                    "self.0
+                .get_strv(\"{}\")
+                .iter()
+                .map(|value| value.to_string())
+                .collect()",
+                    k
+                ),
+                _ => format!("self.0.get_{}(\"{}\")", getter, k),
+            };
+            (
+                ty.to_string(),
+                get,
+                set_ty.to_string(),
+                format!("self.0.set_{}(\"{}\", value)", getter, k),
+            )
+        }
+    };
+
+    let doc = key
+        .summary
+        .as_ref()
+        .map(|summary| {
+            let summary = summary.split_whitespace().collect::<Vec<_>>().join(" ");
+            format!("        /// {}\n", summary)
+        })
+        .unwrap_or_default();
+    format!(
+        "
+{doc}        pub fn {getter}(&self) -> {ty} {{
+            {get}
+        }}
+
+        pub fn set_{setter}(&self, value: {set_ty}) -> Result<(), glib::BoolError> {{
+            {set}
+        }}
+
+        pub fn connect_{setter}_changed<F: Fn(&{name}) + 'static>(
+            &self,
+            f: F,
+        ) -> glib::SignalHandlerId {{
+            self.0.connect_changed(move |settings, key| {{
+                if key == \"{key}\" {{
+                    f(&{name}(settings.clone()))
+                }}
+            }})
+        }}\n",
+        doc = doc,
+        getter = getter,
+        setter = getter.trim_end_matches('_'),
+        ty = ty,
+        get = get,
+        set_ty = set_ty,
+        set = set,
+        name = name,
+        key = k,
+    )
+}
+
+// window-width to WindowWidth
+fn camel_case(name: &str) -> String {
+    name.split(['-', '_', '.'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .collect()
+}
+
+// Nicks like 12h aren't valid identifiers on their own
+fn variant_name(nick: &str) -> String {
+    let name = camel_case(nick);
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        "V".to_string() + &name
+    } else {
+        name
+    }
+}
+
+// window-width to window_width, keywords and
+// names taken by the wrapper get a trailing underscore
+fn method_name(key: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else",
+        "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+        "move", "mut", "new", "pub", "ref", "return", "self", "static", "struct", "super", "trait",
+        "true", "try", "type", "unsafe", "use", "where", "while", "yield",
+    ];
+    let name = key.replace('-', "_");
+    if KEYWORDS.contains(&name.as_str()) {
+        name + "_"
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const SCHEMA: &str = r#"<schemalist>
+  <enum id="io.foo.Demo.Clock">
+    <value nick="12h" value="0"/>
+    <value nick="24h" value="1"/>
+    <value nick="military" value="1"/>
+  </enum>
+  <flags id="io.foo.Demo.Days">
+    <value nick="monday" value="1"/>
+    <value nick="tuesday" value="2"/>
+  </flags>
+  <schema id="io.foo.Demo" path="/io/foo/Demo/">
+    <key name="type" type="s">
+      <default>"plain"</default>
+      <summary>Kind of
+        document</summary>
+    </key>
+    <key name="new" type="b"><default>false</default></key>
+    <key name="window-width" type="i"><default>600</default></key>
+    <key name="recent" type="as"><default>[]</default></key>
+    <key name="clock" enum="io.foo.Demo.Clock"><default>"24h"</default></key>
+    <key name="days" flags="io.foo.Demo.Days"><default>["monday"]</default></key>
+  </schema>
+  <schema id="io.foo.Demo.Window">
+    <key name="maximized" type="b"><default>false</default></key>
+  </schema>
+</schemalist>"#;
+
+    fn generated() -> String {
+        let list = SchemaList::parse(SCHEMA, Path::new("test.gschema.xml"));
+        generate(&list.expect("schema doesn't parse"), "io.foo.Demo")
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(method_name("window-width"), "window_width");
+        assert_eq!(method_name("type"), "type_");
+        assert_eq!(method_name("new"), "new_");
+        assert_eq!(method_name("self"), "self_");
+        assert_eq!(variant_name("12h"), "V12h");
+        assert_eq!(variant_name("dark-mode"), "DarkMode");
+        assert_eq!(struct_name("io.foo.Demo", "io.foo.Demo"), "Settings");
+        assert_eq!(
+            struct_name("io.foo.Demo.Window.Tab", "io.foo.Demo"),
+            "WindowTabSettings"
+        );
+        assert_eq!(
+            struct_name("org.gnome.Other", "io.foo.Demo"),
+            "OtherSettings"
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let code = generated();
+        assert!(
+            code.contains("        /// Kind of document\n        pub fn type_(&self) -> String {")
+        );
+        assert!(code.contains("self.0.get_string(\"type\").to_string()"));
+        assert!(code.contains("pub fn set_type(&self, value: &str)"));
+        assert!(code.contains("pub fn connect_type_changed<F: Fn(&Settings) + 'static>("));
+        assert!(code.contains("if key == \"type\" {"));
+        assert!(code.contains("pub fn new_(&self) -> bool {"));
+        assert!(code.contains("pub fn set_new(&self, value: bool)"));
+        assert!(code.contains("pub fn window_width(&self) -> i32 {"));
+        assert!(code.contains("pub fn set_recent(&self, value: &[&str])"));
+    }
+
+    #[test]
+    fn enums() {
+        let list = SchemaList::parse(SCHEMA, Path::new("test.gschema.xml")).unwrap();
+        // military shares 24h's value, from_value() returns the first
+        assert_eq!(
+            generate_enum(&list, list.enumeration("io.foo.Demo.Clock").unwrap()),
+            r#"
+    #[allow(dead_code)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Clock {
+        V12h,
+        V24h,
+        Military,
+    }
+
+    #[allow(dead_code)]
+    impl Clock {
+        pub const ALL: &'static [Clock] = &[Clock::V12h, Clock::V24h, Clock::Military];
+
+        pub fn value(self) -> i32 {
+            match self {
+                Clock::V12h => 0,
+                Clock::V24h => 1,
+                Clock::Military => 1,
+            }
+        }
+
+        pub fn from_value(value: i32) -> Option<Clock> {
+            match value {
+                0 => Some(Clock::V12h),
+                1 => Some(Clock::V24h),
+                _ => None,
+            }
+        }
+    }
+"#
+        );
+
+        let schema = list.schema("io.foo.Demo").unwrap();
+        let key = schema.keys.iter().find(|key| key.name == "clock").unwrap();
+        assert_eq!(
+            generate_key(&list, "Settings", key),
+            r#"
+        pub fn clock(&self) -> Clock {
+            Clock::from_value(self.0.get_enum("clock")).expect("Invalid enum value")
+        }
+
+        pub fn set_clock(&self, value: Clock) -> Result<(), glib::BoolError> {
+            self.0.set_enum("clock", value.value())
+        }
+
+        pub fn connect_clock_changed<F: Fn(&Settings) + 'static>(
+            &self,
+            f: F,
+        ) -> glib::SignalHandlerId {
+            self.0.connect_changed(move |settings, key| {
+                if key == "clock" {
+                    f(&Settings(settings.clone()))
+                }
+            })
+        }
+"#
+        );
+    }
+
+    #[test]
+    fn enum_names() {
+        let list = SchemaList::parse(
+            r#"<schemalist>
+                <enum id="org.app.Foo.Mode"><value nick="a" value="0"/></enum>
+                <enum id="org.app.Bar.Mode"><value nick="a" value="0"/></enum>
+                <enum id="org.app.Bar.Mode.Style"><value nick="a" value="0"/></enum>
+                <schema id="org.app" path="/org/app/">
+                    <key name="mode" enum="org.app.Bar.Mode"><default>"a"</default></key>
+                </schema>
+            </schemalist>"#,
+            Path::new("test.gschema.xml"),
+        )
+        .unwrap();
+        let names = list
+            .enums
+            .iter()
+            .map(|e| enum_name(&list, &e.id))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["FooMode", "BarMode", "Style"]);
+        let code = generate(&list, "org.app");
+        assert!(code.contains("pub enum FooMode {"));
+        assert!(code.contains("pub enum BarMode {"));
+        assert!(code.contains("pub fn mode(&self) -> BarMode {"));
+    }
+
+    #[test]
+    fn flags() {
+        let code = generated();
+        assert!(code.contains("pub fn value(self) -> u32 {"));
+        assert!(!code.contains("Option<Days>"));
+        assert!(code.contains("pub fn days(&self) -> Vec<Days> {"));
+        assert!(code.contains("let bits = self.0.get_flags(\"days\");"));
+        assert!(code.contains(".filter(|flag| bits & flag.value() != 0)"));
+        assert!(code.contains("pub fn set_days(&self, value: &[Days])"));
+        assert!(code.contains("self.0.set_flags(\"days\", bits)"));
+    }
+
+    #[test]
+    fn schemas() {
+        let code = generated();
+        assert!(code.contains("pub struct Settings(gio::Settings);"));
+        assert!(code.contains("Settings(gio::Settings::new(\"io.foo.Demo\"))"));
+        // No path, so the caller picks one
+        assert!(code.contains("pub struct WindowSettings(gio::Settings);"));
+        assert!(code.contains("pub fn new(path: &str) -> Self {"));
+        assert!(code.contains("gio::Settings::new_with_path(\"io.foo.Demo.Window\", path)"));
+        assert!(code.contains("impl std::ops::Deref for WindowSettings {"));
+    }
+}
@@ -0,0 +1,198 @@
+use super::po::Catalog;
+use roxmltree::{Document, Node, NS_XML_URI};
+
+// Keys msgfmt --desktop translates by default
+const DESKTOP_KEYS: &[&str] = &["Name", "GenericName", "Comment", "Keywords"];
+
+// Elements of a component the AppStream ITS rules translate,
+// besides name, description paragraphs and list items
+const APPDATA_ELEMENTS: &[&str] = &[
+    "summary",
+    "developer_name",
+    "name_variant_suffix",
+    "keyword",
+    "caption",
+];
+
+// Add a Name[de]= line after Name= for every language with a translation,
+// like msgfmt --desktop. Keys translated in the file itself are kept.
+pub fn desktop(text: &str, catalogs: &[(String, Catalog)]) -> String {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if DESKTOP_KEYS.contains(&key.trim()) => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        let id = desktop_unescape(value);
+        let group = group(&lines, i);
+        for (language, catalog) in catalogs {
+            let localized = format!("{}[{}]", key, language);
+            let exists = group
+                .iter()
+                .any(|line| line.split_once('=').map(|(key, _)| key.trim()) == Some(&localized));
+            if let (false, Some(translation)) = (exists, catalog.translate(None, &id)) {
+                out.push_str(&format!("{}={}\n", localized, desktop_escape(translation)));
+            }
+        }
+    }
+    out
+}
+
+// Lines of the [Group] line i belongs to
+fn group<'a>(lines: &[&'a str], i: usize) -> Vec<&'a str> {
+    let is_header = |line: &&str| line.trim_start().starts_with('[');
+    let start = lines[..i].iter().rposition(is_header).unwrap_or(0);
+    let end = lines[i..]
+        .iter()
+        .position(is_header)
+        .map_or(lines.len(), |end| i + end);
+    lines[start..end].to_vec()
+}
+
+// Values escape whitespace and backslashes, e.g. \s for a space
+fn desktop_unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn desktop_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+// Add <name xml:lang="de"> after <name> and so on for every language with
+// a translation, like msgfmt --xml with the AppStream ITS rules. Paragraphs
+// and list items of descriptions are translated one by one, with <em>
+// and <code> as part of the text.
+pub fn appdata(text: &str, catalogs: &[(String, Catalog)]) -> Result<String, String> {
+    let document = Document::parse(text).map_err(|err| err.to_string())?;
+
+    let mut insertions = Vec::new();
+    for node in document.descendants().filter(is_translatable) {
+        let id = message(node)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if id.is_empty() {
+            continue;
+        }
+        let range = node.range();
+        let indent = text[..range.start]
+            .rsplit('\n')
+            .next()
+            .filter(|indent| indent.chars().all(char::is_whitespace))
+            .unwrap_or("");
+        let markup = node.children().any(|child| child.is_element());
+        let name = node.tag_name().name();
+
+        let mut inserted = String::new();
+        for (language, catalog) in catalogs {
+            let exists = node.parent().is_some_and(|parent| {
+                parent.children().any(|sibling| {
+                    sibling.tag_name().name() == name
+                        && sibling.attribute((NS_XML_URI, "lang")) == Some(language.as_str())
+                })
+            });
+            if let (false, Some(translation)) = (exists, catalog.translate(None, &id)) {
+                inserted.push_str(&format!(
+                    "\n{}<{} xml:lang=\"{}\">{}</{}>",
+                    indent,
+                    name,
+                    language,
+                    xml_escape(translation, markup),
+                    name
+                ));
+            }
+        }
+        insertions.push((range.end, inserted));
+    }
+
+    let mut out = text.to_string();
+    for (position, inserted) in insertions.iter().rev() {
+        out.insert_str(*position, inserted);
+    }
+    Ok(out)
+}
+
+fn is_translatable(node: &Node) -> bool {
+    if !node.is_element()
+        || node.attribute((NS_XML_URI, "lang")).is_some()
+        || node
+            .ancestors()
+            .any(|ancestor| ancestor.attribute("translate") == Some("no"))
+    {
+        return false;
+    }
+    let parent = node.parent_element();
+    let parent = parent.as_ref().map(|parent| parent.tag_name().name());
+    match node.tag_name().name() {
+        // <developer><name> as well as <component><name>
+        "name" => parent == Some("component") || parent == Some("developer"),
+        "p" => parent == Some("description"),
+        "li" => node
+            .ancestors()
+            .nth(2)
+            .is_some_and(|ancestor| ancestor.tag_name().name() == "description"),
+        name => APPDATA_ELEMENTS.contains(&name),
+    }
+}
+
+// Text of the element with inline markup, as xgettext extracts it
+fn message(node: Node) -> String {
+    let mut out = String::new();
+    for child in node.children() {
+        if child.is_text() {
+            out.push_str(child.text().unwrap_or(""));
+        } else if child.is_element() {
+            let name = child.tag_name().name();
+            out.push_str(&format!("<{}>{}</{}>", name, message(child), name));
+        }
+    }
+    out
+}
+
+// Translations with markup keep their tags, only stray & are escaped
+fn xml_escape(text: &str, markup: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        match c {
+            '&' if !markup || !is_entity(&text[i..]) => out.push_str("&amp;"),
+            '<' if !markup => out.push_str("&lt;"),
+            '>' if !markup => out.push_str("&gt;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// &amp; or &#38;
+fn is_entity(text: &str) -> bool {
+    match text[1..].split_once(';') {
+        Some((name, _)) => {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#')
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,131 @@
+use super::fingerprint::read_dir_sorted;
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO;
+
+// Size of struct inotify_event without its name
+const EVENT_SIZE: usize = 16;
+
+// inotify watches on directory trees, new
+// subdirectories are watched as they appear.
+pub struct Watcher {
+    fd: i32,
+    dirs: HashMap<i32, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(dirs: &[PathBuf]) -> Option<Watcher> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+
+        let mut watcher = Watcher {
+            fd,
+            dirs: HashMap::new(),
+        };
+        for dir in dirs {
+            watcher.add(dir);
+        }
+        Some(watcher)
+    }
+
+    fn add(&mut self, dir: &Path) {
+        if !dir.is_dir() {
+            return;
+        }
+        if let Ok(path) = CString::new(dir.as_os_str().as_bytes()) {
+            let wd = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), MASK) };
+            if wd >= 0 {
+                self.dirs.insert(wd, dir.to_path_buf());
+            }
+        }
+        for entry in read_dir_sorted(dir) {
+            if entry.is_dir() {
+                self.add(&entry);
+            }
+        }
+    }
+
+    // Block until files change, then until nothing changed
+    // for delay, so a save touching many files is one rebuild.
+    pub fn wait(&mut self, delay: Duration) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        while changed.is_empty() {
+            let mut timeout = -1;
+            while self.poll(timeout) {
+                for path in self.read() {
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+                timeout = delay.as_millis() as i32;
+            }
+        }
+        changed
+    }
+
+    fn poll(&self, timeout: i32) -> bool {
+        let mut fd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fd, 1, timeout) > 0 }
+    }
+
+    fn read(&mut self) -> Vec<PathBuf> {
+        let mut buffer = [0u8; 4096];
+        let len = unsafe { libc::read(self.fd, buffer.as_mut_ptr() as *mut _, buffer.len()) };
+        if len <= 0 {
+            return Vec::new();
+        }
+
+        let mut changed = Vec::new();
+        let mut offset = 0;
+        while offset + EVENT_SIZE <= len as usize {
+            let field = |at: usize| {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(&buffer[offset + at..offset + at + 4]);
+                u32::from_ne_bytes(bytes)
+            };
+            let (wd, mask, namelen) = (field(0) as i32, field(4), field(12) as usize);
+            let name = &buffer[offset + EVENT_SIZE..offset + EVENT_SIZE + namelen];
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+            offset += EVENT_SIZE + namelen;
+
+            let path = match self.dirs.get(&wd) {
+                Some(dir) => dir.join(std::ffi::OsStr::from_bytes(name)),
+                None => continue,
+            };
+            if mask & libc::IN_ISDIR != 0 && mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                self.add(&path);
+            }
+            if !is_temporary(name) {
+                changed.push(path);
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+// Swap and backup files editors write next to the real one
+fn is_temporary(name: &[u8]) -> bool {
+    name.starts_with(b".") || name.ends_with(b"~") || name.ends_with(b".swp") || name == b"4913"
+}
@@ -0,0 +1,50 @@
+use cargo_pkg::{Builder, Metadata, Step};
+use std::path::Path;
+
+// Copy the fixture so the build doesn't write into the source tree
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            std::fs::copy(&path, &target).unwrap();
+        }
+    }
+}
+
+#[test]
+fn merges_translations_without_msgfmt() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/translate");
+    let dir = std::env::temp_dir().join(format!("cargo-pkg-appdata-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    copy_dir(&fixture, &dir);
+
+    let metadata = Metadata::from(dir.join("Cargo.toml").to_str().unwrap()).unwrap();
+    let flags = Vec::new();
+    let mut builder = Builder::new(&flags, "release", true).with_root(&dir);
+    builder.steps = vec![Step::ConfigFiles, Step::AppdataDesktop];
+    assert!(builder.build(&metadata, &dir.join("_build")));
+
+    for (installed, expected) in &[
+        (
+            "_build/share/applications/io.example.Demo.desktop",
+            "expected/io.example.Demo.desktop",
+        ),
+        (
+            "_build/share/appdata/io.example.Demo.appdata.xml",
+            "expected/io.example.Demo.appdata.xml",
+        ),
+    ] {
+        assert_eq!(
+            std::fs::read_to_string(dir.join(installed)).unwrap(),
+            std::fs::read_to_string(fixture.join(expected)).unwrap(),
+            "{}",
+            installed
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+use cargo_pkg::{Builder, Metadata, Step};
+
+const CARGO_TOML: &str = r#"[package]
+name = "demo-app"
+version = "0.1.0"
+
+[package.metadata.pkg]
+id = "io.example.Demo"
+name = "Demo"
+gettext-domain = "demo-domain"
+"#;
+
+const DE_PO: &str = r#"msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+msgid "Hello"
+msgstr "Hallo"
+"#;
+
+#[test]
+fn installs_mo_under_gettext_domain() {
+    let dir = std::env::temp_dir().join(format!("cargo-pkg-translations-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("po")).unwrap();
+    std::fs::write(dir.join("Cargo.toml"), CARGO_TOML).unwrap();
+    std::fs::write(dir.join("po/LINGUAS"), "de\n").unwrap();
+    std::fs::write(dir.join("po/de.po"), DE_PO).unwrap();
+
+    let metadata = Metadata::from(dir.join("Cargo.toml").to_str().unwrap()).unwrap();
+    assert_eq!(metadata.gettext_domain, "demo-domain");
+
+    let flags = Vec::new();
+    let mut builder = Builder::new(&flags, "release", true).with_root(&dir);
+    builder.steps = vec![Step::Translations];
+    assert!(builder.build(&metadata, &dir.join("_build")));
+
+    let modir = dir.join("_build/share/locale/de/LC_MESSAGES");
+    assert!(modir.join("demo-domain.mo").exists());
+    assert!(!modir.join("de.mo").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}